@@ -1,5 +1,21 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::collections::VecDeque;
+use std::time::Instant;
+use trade_match::matching_engine::ladder_book::LadderBook;
 use trade_match::matching_engine::market::*;
+use trade_match::matching_engine::order_book::OrderBook;
+use trade_match::matching_engine::price::Price;
+use trade_match::matching_engine::quantity::Quantity;
+
+// Market with room for the 1M order scenarios reserved up front
+fn pooled_market() -> Market {
+    let config = MarketConfig {
+        order_capacity: 1 << 20,
+        ..MarketConfig::default()
+    };
+
+    Market::with_config("BTCUSD", config).unwrap()
+}
 
 fn benchmark_add_limit_bids_many_levels(c: &mut Criterion) {
     let mut market = Market::new("BTCUSD");
@@ -8,7 +24,12 @@ fn benchmark_add_limit_bids_many_levels(c: &mut Criterion) {
     c.bench_function("add_limit_bids_many_levels", |b| {
         b.iter(|| {
             for i in 0..100_000 {
-                market.add_limit_bid(100.0 + i as f32, 10.0).unwrap();
+                market
+                    .add_limit_bid(
+                        Price::from_ticks(10_000 + i * 100),
+                        Quantity::from_lots(1_000),
+                    )
+                    .unwrap();
             }
         })
     });
@@ -21,7 +42,12 @@ fn benchmark_add_limit_asks_many_levels(c: &mut Criterion) {
     c.bench_function("add_limit_asks_many_levels", |b| {
         b.iter(|| {
             for i in 0..100_000 {
-                market.add_limit_ask(100.0 + i as f32, 10.0).unwrap();
+                market
+                    .add_limit_ask(
+                        Price::from_ticks(10_000 + i * 100),
+                        Quantity::from_lots(1_000),
+                    )
+                    .unwrap();
             }
         })
     });
@@ -34,7 +60,9 @@ fn benchmark_add_limit_bids_single_level(c: &mut Criterion) {
     c.bench_function("add_limit_bids_single_level", |b| {
         b.iter(|| {
             for _i in 0..100_000 {
-                market.add_limit_bid(100.0, 10.0).unwrap();
+                market
+                    .add_limit_bid(Price::from_ticks(10_000), Quantity::from_lots(1_000))
+                    .unwrap();
             }
         })
     });
@@ -47,26 +75,147 @@ fn benchmark_add_limit_asks_single_level(c: &mut Criterion) {
     c.bench_function("add_limit_asks_single_level", |b| {
         b.iter(|| {
             for _i in 0..100_000 {
-                market.add_limit_ask(100.0, 10.0).unwrap();
+                market
+                    .add_limit_ask(Price::from_ticks(10_000), Quantity::from_lots(1_000))
+                    .unwrap();
             }
         })
     });
 }
 
-fn benchmark_cancel_order(c: &mut Criterion) {
-    let mut market = Market::new("BTCUSD");
-
-    // Prepopulate the market with 1,000,000 orders
+// Cancels orders from a level of 1,000,000 bids, the orders to cancel are
+// added before the timer starts so the book never runs dry
+fn cancel_orders(c: &mut Criterion, name: &str, mut market: Market) {
     for _i in 0..1_000_000 {
-        market.add_limit_bid(100.0, 10.0).unwrap();
+        market
+            .add_limit_bid(Price::from_ticks(10_000), Quantity::from_lots(1_000))
+            .unwrap();
     }
 
-    let mut counter = 1;
+    c.bench_function(name, |b| {
+        b.iter_custom(|iterations| {
+            let ids: Vec<u64> = (0..iterations)
+                .map(|_| {
+                    market
+                        .add_limit_bid(Price::from_ticks(10_000), Quantity::from_lots(1_000))
+                        .unwrap()
+                        .order_id()
+                })
+                .collect();
 
-    c.bench_function("cancel_order", |b| {
-        b.iter(|| {
-            market.cancel_limit_order(black_box(counter));
-            counter += 1;
+            let start = Instant::now();
+
+            for id in ids {
+                market.cancel_limit_order(black_box(id)).unwrap();
+            }
+
+            start.elapsed()
+        })
+    });
+}
+
+fn benchmark_cancel_order(c: &mut Criterion) {
+    cancel_orders(c, "cancel_order", Market::new("BTCUSD"));
+}
+
+fn benchmark_cancel_order_pooled(c: &mut Criterion) {
+    cancel_orders(c, "cancel_order_pooled", pooled_market());
+}
+
+fn benchmark_cancel_order_deep_level(c: &mut Criterion) {
+    let mut market = pooled_market();
+    let mut live = VecDeque::new();
+
+    c.bench_function("cancel_order_deep_level", |b| {
+        b.iter_custom(|iterations| {
+            // keep 1M orders queued at a single level and cancel from the middle of the queue
+            while live.len() < 1_000_000 + iterations as usize {
+                let report = market
+                    .add_limit_bid(Price::from_ticks(10_000), Quantity::from_lots(1_000))
+                    .unwrap();
+                live.push_back(report.order_id());
+            }
+
+            let first = (live.len() - iterations as usize) / 2;
+            let ids: Vec<u64> = live.drain(first..first + iterations as usize).collect();
+
+            let start = Instant::now();
+
+            for id in ids {
+                market.cancel_limit_order(black_box(id)).unwrap();
+            }
+
+            start.elapsed()
+        })
+    });
+}
+
+// Baseline for cancel_order_deep_level: a level that queues the slab keys of
+// its orders in a VecDeque sorted by order sequence, as levels did before
+// orders were linked to their neighbours. A cancel binary searches the queue
+// through the slab and shifts the orders behind it. Compare the two with
+// `cargo bench --bench market -- cancel_order_deep_level`.
+fn benchmark_cancel_order_deep_level_baseline(c: &mut Criterion) {
+    // sequence of the order under each slab key, keys are never reused here
+    let mut sequences: Vec<u64> = Vec::new();
+    let mut queue: VecDeque<usize> = VecDeque::new();
+
+    c.bench_function("cancel_order_deep_level_baseline", |b| {
+        b.iter_custom(|iterations| {
+            while queue.len() < 1_000_000 + iterations as usize {
+                queue.push_back(sequences.len());
+                sequences.push(sequences.len() as u64);
+            }
+
+            let first = (queue.len() - iterations as usize) / 2;
+            let keys: Vec<usize> = queue
+                .range(first..first + iterations as usize)
+                .copied()
+                .collect();
+
+            let start = Instant::now();
+
+            for key in keys {
+                let sequence = sequences[black_box(key)];
+                let position = queue
+                    .binary_search_by_key(&sequence, |key| sequences[*key])
+                    .unwrap();
+                queue.remove(position);
+            }
+
+            start.elapsed()
+        })
+    });
+}
+
+fn benchmark_add_limit_bids_pooled(c: &mut Criterion) {
+    // 1M bids each at a different price level
+    c.bench_function("add_limit_bids_1m", |b| {
+        b.iter_with_large_setup(
+            || Market::new("BTCUSD"),
+            |mut market| {
+                for i in 0..1_000_000 {
+                    market
+                        .add_limit_bid(
+                            Price::from_ticks(10_000 + i * 100),
+                            Quantity::from_lots(1_000),
+                        )
+                        .unwrap();
+                }
+            },
+        )
+    });
+
+    c.bench_function("add_limit_bids_1m_pooled", |b| {
+        b.iter_with_large_setup(pooled_market, |mut market| {
+            for i in 0..1_000_000 {
+                market
+                    .add_limit_bid(
+                        Price::from_ticks(10_000 + i * 100),
+                        Quantity::from_lots(1_000),
+                    )
+                    .unwrap();
+            }
         })
     });
 }
@@ -76,32 +225,94 @@ fn benchmark_execute_limit_bid(c: &mut Criterion) {
 
     // Prepopulate the market with 1,000,000 ask orders
     for i in 1..1_000_000 {
-        market.add_limit_ask(100.0 + i as f32, 100_000.0).unwrap();
+        market
+            .add_limit_ask(
+                Price::from_ticks(10_000 + i * 100),
+                Quantity::from_lots(10_000_000),
+            )
+            .unwrap();
     }
 
     c.bench_function("execute_limit_bid", |b| {
         b.iter(|| {
             // This should execute against existing asks
             market
-                .add_limit_bid(black_box(120_000.0), black_box(1000.0))
+                .add_limit_bid(
+                    black_box(Price::from_ticks(12_000_000)),
+                    black_box(Quantity::from_lots(100_000)),
+                )
                 .unwrap();
         })
     });
 }
 
+// Asks on every tick of a dense price range behind the spread, each iteration
+// adds ten levels at the front of the book and sweeps them with one bid
+fn execute_dense_bids<B: OrderBook>(c: &mut Criterion, name: &str, mut market: Market<B>) {
+    for i in 0..190_000 {
+        market
+            .add_limit_ask(Price::from_ticks(20_000 + i), Quantity::from_lots(100))
+            .unwrap();
+    }
+
+    c.bench_function(name, |b| {
+        b.iter(|| {
+            for i in 0..10 {
+                market
+                    .add_limit_ask(Price::from_ticks(10_000 + i), Quantity::from_lots(100))
+                    .unwrap();
+            }
+
+            market
+                .add_limit_bid(
+                    black_box(Price::from_ticks(10_009)),
+                    black_box(Quantity::from_lots(1_000)),
+                )
+                .unwrap();
+        })
+    });
+}
+
+fn benchmark_execute_limit_bid_dense(c: &mut Criterion) {
+    execute_dense_bids(c, "execute_limit_bid_dense", Market::new("BTCUSD"));
+}
+
+fn benchmark_execute_limit_bid_dense_ladder(c: &mut Criterion) {
+    let config = MarketConfig::default();
+    let book = LadderBook::new(
+        Price::from_ticks(10_000),
+        Price::from_ticks(210_000),
+        config.tick_size,
+    );
+
+    execute_dense_bids(
+        c,
+        "execute_limit_bid_dense_ladder",
+        Market::with_book(0, "BTCUSD", config, book).unwrap(),
+    );
+}
+
 fn benchmark_execute_limit_ask(c: &mut Criterion) {
     let mut market = Market::new("BTCUSD");
 
     // Prepopulate the market with 1,000,000 bid orders
     for i in 1..1_000_000 {
-        market.add_limit_bid(100.0 + i as f32, 100_000.0).unwrap();
+        market
+            .add_limit_bid(
+                Price::from_ticks(10_000 + i * 100),
+                Quantity::from_lots(10_000_000),
+            )
+            .unwrap();
     }
 
     c.bench_function("execute_limit_ask", |b| {
         b.iter(|| {
             // This should execute against existing bids
             market
-                .add_limit_ask(black_box(99.0), black_box(1000.0))
+                .add_limit_ask(
+                    black_box(Price::from_ticks(9_900)),
+                    black_box(Quantity::from_lots(100_000)),
+                )
                 .unwrap();
         })
     });
@@ -112,13 +323,43 @@ fn benchmark_add_market_bid(c: &mut Criterion) {
 
     // Prepopulate the market with 1,000,000 ask orders
     for i in 1..1_000_000 {
-        market.add_limit_ask(100.0 + i as f32, 100_000.0).unwrap();
+        market
+            .add_limit_ask(
+                Price::from_ticks(10_000 + i * 100),
+                Quantity::from_lots(10_000_000),
+            )
+            .unwrap();
     }
 
     c.bench_function("add_market_bid", |b| {
         b.iter(|| {
             // This should execute against existing asks
-            market.add_market_bid(black_box(1000.0));
+            market
+                .add_market_bid(black_box(Quantity::from_lots(100_000)))
+                .unwrap();
+        })
+    });
+}
+
+fn benchmark_add_market_bid_pooled(c: &mut Criterion) {
+    let mut market = pooled_market();
+
+    // Prepopulate the market with 1,000,000 ask orders
+    for i in 1..1_000_000 {
+        market
+            .add_limit_ask(
+                Price::from_ticks(10_000 + i * 100),
+                Quantity::from_lots(10_000_000),
+            )
+            .unwrap();
+    }
+
+    c.bench_function("add_market_bid_pooled", |b| {
+        b.iter(|| {
+            // This should execute against existing asks
+            market
+                .add_market_bid(black_box(Quantity::from_lots(100_000)))
+                .unwrap();
         })
     });
 }
@@ -128,13 +369,20 @@ fn benchmark_add_market_ask(c: &mut Criterion) {
 
     // Prepopulate the market with 1,000,000 bid orders
     for i in 0..1_000_000 {
-        market.add_limit_bid(100.0 + i as f32, 100_000.0).unwrap();
+        market
+            .add_limit_bid(
+                Price::from_ticks(10_000 + i * 100),
+                Quantity::from_lots(10_000_000),
+            )
+            .unwrap();
     }
 
     c.bench_function("add_market_ask", |b| {
         b.iter(|| {
             // This should execute against existing bids
-            market.add_market_ask(black_box(1000.0));
+            market
+                .add_market_ask(black_box(Quantity::from_lots(100_000)))
+                .unwrap();
         })
     });
 }
@@ -146,9 +394,16 @@ criterion_group!(
     benchmark_add_limit_bids_single_level,
     benchmark_add_limit_asks_single_level,
     benchmark_cancel_order,
+    benchmark_cancel_order_pooled,
+    benchmark_cancel_order_deep_level,
+    benchmark_cancel_order_deep_level_baseline,
+    benchmark_add_limit_bids_pooled,
     benchmark_execute_limit_bid,
+    benchmark_execute_limit_bid_dense,
+    benchmark_execute_limit_bid_dense_ladder,
     benchmark_execute_limit_ask,
     benchmark_add_market_bid,
+    benchmark_add_market_bid_pooled,
     benchmark_add_market_ask
 );
 
@@ -0,0 +1,102 @@
+use std::env;
+use std::io::{self, BufWriter, Write};
+use std::process;
+use trade_match::journal::{JournalEntry, JournalReader};
+use trade_match::matching_engine::command::{encode_side, CommandResult};
+use trade_match::matching_engine::error::MarketError;
+use trade_match::matching_engine::market::Market;
+use trade_match::replay::{self, fills, StateDigest};
+
+const USAGE: &str = "usage: replay JOURNAL";
+
+// Replays a journal into a fresh market and prints every fill, every rejected
+// command and the state digest after each command, one record per line:
+//
+//   fill SEQUENCE TRADE_ID AGGRESSOR_ID PASSIVE_ID PRICE_TICKS QUANTITY_LOTS SIDE
+//        AGGRESSOR_ACCOUNT PASSIVE_ACCOUNT
+//   reject SEQUENCE REASON
+//   state SEQUENCE DIGEST
+//
+// The market is built with the id, symbol and config in the journal header.
+// The output of two runs or two builds can be compared with diff.
+fn main() {
+    let mut args = env::args().skip(1);
+
+    let path = match (args.next(), args.next()) {
+        (Some(path), None) if !path.starts_with("--") => path,
+        _ => usage(),
+    };
+
+    let reader = match JournalReader::open(&path) {
+        Ok(reader) => reader,
+        Err(error) => {
+            eprintln!("Failed to open journal {}: {}", path, error);
+            process::exit(1);
+        }
+    };
+
+    let header = reader.header();
+    let mut market =
+        match Market::with_market_id(header.market_id(), header.symbol(), *header.config()) {
+            Ok(market) => market,
+            Err(error) => {
+                eprintln!("Failed to set up the market of {}: {}", path, error);
+                process::exit(1);
+            }
+        };
+    let mut output = BufWriter::new(io::stdout().lock());
+    let mut failure = None;
+
+    let digest = replay::replay(reader, &mut market, |entry, result, digest| {
+        if failure.is_none() {
+            failure = write_step(&mut output, entry, result, digest).err();
+        }
+    });
+
+    let result = digest.and_then(|digest| match failure {
+        Some(error) => Err(error),
+        None => {
+            writeln!(output, "digest {:016x}", digest.value())?;
+            output.flush()
+        }
+    });
+
+    if let Err(error) = result {
+        eprintln!("Replay failed: {}", error);
+        process::exit(1);
+    }
+}
+
+fn write_step<W: Write>(
+    output: &mut W,
+    entry: &JournalEntry,
+    result: &Result<CommandResult, MarketError>,
+    digest: StateDigest,
+) -> io::Result<()> {
+    for fill in fills(result) {
+        writeln!(
+            output,
+            "fill {} {} {} {} {} {} {} {} {}",
+            entry.sequence(),
+            fill.trade_id(),
+            fill.aggressor_id(),
+            fill.passive_id(),
+            fill.price().ticks(),
+            fill.quantity().lots(),
+            encode_side(fill.aggressor_side()),
+            fill.aggressor_owner().account(),
+            fill.passive_owner().account()
+        )?;
+    }
+
+    if let Err(error) = result {
+        writeln!(output, "reject {} {}", entry.sequence(), error)?;
+    }
+
+    writeln!(output, "state {} {:016x}", entry.sequence(), digest.value())
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
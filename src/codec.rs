@@ -0,0 +1,164 @@
+use std::io::{self, Read, Write};
+
+// Frames larger than this are treated as corrupt input
+pub const MAX_FRAME_SIZE: usize = 1 << 20;
+
+// Big-endian binary encoder used by the wire protocol
+#[derive(Debug, Default)]
+pub struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Encoder { bytes: Vec::new() }
+    }
+
+    pub fn u8(&mut self, value: u8) -> &mut Self {
+        self.bytes.push(value);
+        self
+    }
+
+    pub fn u16(&mut self, value: u16) -> &mut Self {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn u64(&mut self, value: u64) -> &mut Self {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn i64(&mut self, value: i64) -> &mut Self {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    // Strings are limited to 255 bytes and prefixed with their length
+    pub fn string(&mut self, value: &str) -> &mut Self {
+        let length = value.len().min(u8::MAX as usize);
+        self.bytes.push(length as u8);
+        self.bytes.extend_from_slice(&value.as_bytes()[..length]);
+        self
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+#[derive(Debug)]
+pub struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Decoder { bytes, position: 0 }
+    }
+
+    pub fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    pub fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_be_bytes(self.take()?))
+    }
+
+    pub fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_be_bytes(self.take()?))
+    }
+
+    pub fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_be_bytes(self.take()?))
+    }
+
+    pub fn i64(&mut self) -> io::Result<i64> {
+        Ok(i64::from_be_bytes(self.take()?))
+    }
+
+    pub fn string(&mut self) -> io::Result<String> {
+        let length = self.u8()? as usize;
+        let bytes = self.slice(length)?;
+
+        String::from_utf8(bytes.to_vec()).map_err(|_| invalid_data("string is not valid UTF-8"))
+    }
+
+    // Fails if there are unread bytes left
+    pub fn finish(&self) -> io::Result<()> {
+        match self.position == self.bytes.len() {
+            true => Ok(()),
+            false => Err(invalid_data("trailing bytes")),
+        }
+    }
+
+    fn take<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.slice(N)?);
+        Ok(array)
+    }
+
+    fn slice(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        if self.bytes.len() - self.position < length {
+            return Err(invalid_data("unexpected end of message"));
+        }
+
+        let slice = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        Ok(slice)
+    }
+}
+
+// CRC-32 (IEEE) of a byte slice, used to detect corrupt records on disk
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for byte in bytes {
+        crc ^= *byte as u32;
+
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+
+    !crc
+}
+
+pub fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Writes a payload prefixed with its length as a big-endian u32
+pub fn write_frame<W: Write>(writer: &mut W, payload: &[u8]) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 4);
+    frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame)
+}
+
+// Reads a length-prefixed payload, returns None if the stream ended cleanly before a new frame
+pub fn read_frame<R: Read>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut length = [0; 4];
+
+    match reader.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error),
+    }
+
+    let length = u32::from_be_bytes(length) as usize;
+
+    if length > MAX_FRAME_SIZE {
+        return Err(invalid_data("frame too large"));
+    }
+
+    let mut payload = vec![0; length];
+    reader.read_exact(&mut payload)?;
+    Ok(Some(payload))
+}
@@ -0,0 +1,448 @@
+use crate::codec::{crc32, invalid_data, Decoder, Encoder, MAX_FRAME_SIZE};
+use crate::matching_engine::command::Command;
+use crate::matching_engine::market::{Market, MarketConfig};
+use crate::matching_engine::order_book::OrderBook;
+use crate::snapshot::{decode_config, encode_config};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+// Size of the length and checksum that precede every record
+const HEADER_SIZE: usize = 8;
+
+const MAGIC: &[u8; 4] = b"TMJN";
+
+// Bumped whenever the record layout changes, older versions are not read
+pub const JOURNAL_VERSION: u16 = 1;
+
+// Size of the magic, version, length and checksum a journal file starts with
+const FILE_HEADER_SIZE: usize = MAGIC.len() + 2 + HEADER_SIZE;
+
+// When appended records are flushed to stable storage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    // Before every append returns
+    Always,
+    // After every n appends, a crash can lose up to n - 1 commands
+    Every(u64),
+    // Left to the operating system
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JournalEntry {
+    sequence: u64,
+    command: Command,
+}
+
+impl JournalEntry {
+    pub fn new(sequence: u64, command: Command) -> Self {
+        JournalEntry { sequence, command }
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn command(&self) -> Command {
+        self.command
+    }
+}
+
+// Market a journal was written for. Its commands only rebuild the same book
+// in a market with the same id, as order ids carry it, and the same config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalHeader {
+    market_id: u16,
+    symbol: String,
+    config: MarketConfig,
+}
+
+impl JournalHeader {
+    pub fn new(market_id: u16, symbol: impl Into<String>, config: MarketConfig) -> Self {
+        JournalHeader {
+            market_id,
+            symbol: symbol.into(),
+            config,
+        }
+    }
+
+    pub fn from_market<B: OrderBook>(market: &Market<B>) -> Self {
+        JournalHeader::new(market.market_id(), market.symbol(), *market.config())
+    }
+
+    pub fn market_id(&self) -> u16 {
+        self.market_id
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn config(&self) -> &MarketConfig {
+        &self.config
+    }
+
+    // Magic bytes, version, then a length and CRC-32 protected body
+    fn encode(&self) -> Vec<u8> {
+        let mut body = Encoder::new();
+        body.u16(self.market_id).string(&self.symbol);
+        encode_config(&self.config, &mut body);
+        let body = body.into_bytes();
+
+        let mut header = Encoder::new();
+        header
+            .u16(JOURNAL_VERSION)
+            .u32(body.len() as u32)
+            .u32(crc32(&body));
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&header.into_bytes());
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    // Reads the header a journal starts with and returns it with its size. A
+    // file that ends within it fails with UnexpectedEof.
+    fn read<R: Read>(reader: &mut R) -> io::Result<(JournalHeader, u64)> {
+        let incomplete =
+            || io::Error::new(io::ErrorKind::UnexpectedEof, "journal header is incomplete");
+        let mut prefix = [0; FILE_HEADER_SIZE];
+
+        if !read_complete(reader, &mut prefix)? {
+            return Err(incomplete());
+        }
+
+        if !prefix.starts_with(MAGIC) {
+            return Err(invalid_data("not a market journal"));
+        }
+
+        let mut decoder = Decoder::new(&prefix[MAGIC.len()..]);
+
+        if decoder.u16()? != JOURNAL_VERSION {
+            return Err(invalid_data("unsupported journal version"));
+        }
+
+        let length = decoder.u32()? as usize;
+        let checksum = decoder.u32()?;
+
+        if length > MAX_FRAME_SIZE {
+            return Err(invalid_data("journal header is damaged"));
+        }
+
+        let mut body = vec![0; length];
+
+        if !read_complete(reader, &mut body)? {
+            return Err(incomplete());
+        }
+
+        if crc32(&body) != checksum {
+            return Err(invalid_data("journal header is damaged"));
+        }
+
+        let mut decoder = Decoder::new(&body);
+
+        let header = JournalHeader {
+            market_id: decoder.u16()?,
+            symbol: decoder.string()?,
+            config: decode_config(&mut decoder)?,
+        };
+
+        decoder.finish()?;
+        Ok((header, (FILE_HEADER_SIZE + length) as u64))
+    }
+}
+
+// Append-only log of the commands sent to a market. The file starts with the
+// magic bytes "TMJN", a big-endian u16 format version and the journal header,
+// framed like a record. Every record holds a big-endian u32 length, a CRC-32 of the body, then a body made of the
+// record's sequence number and the encoded command. Sequence numbers start
+// at 1 and have no gaps.
+#[derive(Debug)]
+pub struct Journal {
+    file: File,
+    policy: FsyncPolicy,
+    sequence: u64,
+    // Bytes of complete records in the file
+    length: u64,
+    unsynced: u64,
+}
+
+impl Journal {
+    // Opens or creates the journal of a market and continues after its last
+    // complete record, a record cut short by a crash is discarded. An existing
+    // journal must have been written for the same market.
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        policy: FsyncPolicy,
+        header: &JournalHeader,
+    ) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let (sequence, length) = match JournalReader::new(BufReader::new(&mut file)) {
+            Ok(mut reader) => {
+                if reader.header() != header {
+                    return Err(invalid_data("journal belongs to another market"));
+                }
+
+                for entry in reader.by_ref() {
+                    entry?;
+                }
+
+                (reader.sequence(), reader.valid_length())
+            }
+            // a new journal, or one cut short while it was created, starts over
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                let bytes = header.encode();
+
+                file.set_len(0)?;
+                file.seek(SeekFrom::Start(0))?;
+                file.write_all(&bytes)?;
+
+                (0, bytes.len() as u64)
+            }
+            Err(error) => return Err(error),
+        };
+
+        file.set_len(length)?;
+        file.seek(SeekFrom::Start(length))?;
+
+        Ok(Journal {
+            file,
+            policy,
+            sequence,
+            length,
+            unsynced: 0,
+        })
+    }
+
+    // Writes a command ahead of it being applied and returns its sequence number
+    pub fn append(&mut self, command: &Command) -> io::Result<u64> {
+        let sequence = self.sequence + 1;
+
+        let mut body = Encoder::new();
+        body.u64(sequence);
+        command.encode(&mut body);
+        let body = body.into_bytes();
+
+        let mut record = Encoder::new();
+        record.u32(body.len() as u32).u32(crc32(&body));
+        let mut record = record.into_bytes();
+        record.extend_from_slice(&body);
+
+        if let Err(error) = self.file.write_all(&record) {
+            // drop a partly written record so the next append starts cleanly
+            self.file.set_len(self.length)?;
+            self.file.seek(SeekFrom::Start(self.length))?;
+            return Err(error);
+        }
+
+        self.sequence = sequence;
+        self.length += record.len() as u64;
+        self.unsynced += 1;
+
+        match self.policy {
+            FsyncPolicy::Always => self.sync()?,
+            FsyncPolicy::Every(count) if self.unsynced >= count => self.sync()?,
+            _ => {}
+        }
+
+        Ok(sequence)
+    }
+
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.file.sync_data()?;
+        self.unsynced = 0;
+        Ok(())
+    }
+
+    // Sequence number of the last record, 0 if the journal is empty
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn policy(&self) -> FsyncPolicy {
+        self.policy
+    }
+}
+
+impl Drop for Journal {
+    fn drop(&mut self) {
+        if self.unsynced > 0 {
+            let _ = self.sync();
+        }
+    }
+}
+
+// Journal file of a symbol within a journal directory
+pub fn path<P: AsRef<Path>>(directory: P, symbol: &str) -> PathBuf {
+    directory.as_ref().join(format!("{}.journal", symbol))
+}
+
+// Applies the commands of a journal that come after sequence number `after`
+// to a market that has no journal attached, commands rejected the first time
+// are rejected again. Returns the sequence number of the last command in the
+// journal, a missing journal counts as empty. The journal must have been
+// written for a market with the same id, symbol and config.
+pub fn replay<P: AsRef<Path>, B: OrderBook>(
+    path: P,
+    market: &mut Market<B>,
+    after: u64,
+) -> io::Result<u64> {
+    debug_assert!(market.journal().is_none());
+
+    let reader = match JournalReader::open(path) {
+        Ok(reader) => reader,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(error) => return Err(error),
+    };
+
+    reader.check_market(market)?;
+
+    let mut sequence = 0;
+
+    for entry in reader {
+        let entry = entry?;
+        sequence = entry.sequence();
+
+        if sequence > after {
+            let _ = market.apply(entry.command());
+        }
+    }
+
+    Ok(sequence)
+}
+
+// Reads the records of a journal in order. A last record that is cut short
+// or fails its checksum is taken to be a write interrupted by a crash and ends
+// the journal, a damaged record with more data after it is an error.
+#[derive(Debug)]
+pub struct JournalReader<R> {
+    reader: R,
+    header: JournalHeader,
+    sequence: u64,
+    valid_length: u64,
+    finished: bool,
+}
+
+impl JournalReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        JournalReader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> JournalReader<R> {
+    // Reads the journal header, the records are read as the reader is iterated
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let (header, valid_length) = JournalHeader::read(&mut reader)?;
+
+        Ok(JournalReader {
+            reader,
+            header,
+            sequence: 0,
+            valid_length,
+            finished: false,
+        })
+    }
+
+    pub fn header(&self) -> &JournalHeader {
+        &self.header
+    }
+
+    // Fails unless the journal was written for a market like this one
+    pub fn check_market<B: OrderBook>(&self, market: &Market<B>) -> io::Result<()> {
+        match self.header == JournalHeader::from_market(market) {
+            true => Ok(()),
+            false => Err(invalid_data("journal belongs to another market")),
+        }
+    }
+
+    // Sequence number of the last record read
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    // Bytes taken up by the file header and the records read so far
+    pub fn valid_length(&self) -> u64 {
+        self.valid_length
+    }
+
+    fn read_entry(&mut self) -> io::Result<Option<JournalEntry>> {
+        let mut header = [0; HEADER_SIZE];
+
+        if !read_complete(&mut self.reader, &mut header)? {
+            return Ok(None);
+        }
+
+        let mut decoder = Decoder::new(&header);
+        let length = decoder.u32()? as usize;
+        let checksum = decoder.u32()?;
+
+        if length > MAX_FRAME_SIZE {
+            io::copy(&mut (&mut self.reader).take(length as u64), &mut io::sink())?;
+            return self.damaged_record();
+        }
+
+        let mut body = vec![0; length];
+
+        if !read_complete(&mut self.reader, &mut body)? {
+            return Ok(None);
+        }
+
+        if crc32(&body) != checksum {
+            return self.damaged_record();
+        }
+
+        let mut decoder = Decoder::new(&body);
+        let sequence = decoder.u64()?;
+
+        if sequence != self.sequence + 1 {
+            return Err(invalid_data("journal sequence numbers are not contiguous"));
+        }
+
+        let command = Command::decode(&mut decoder)?;
+        decoder.finish()?;
+
+        self.sequence = sequence;
+        self.valid_length += (HEADER_SIZE + length) as u64;
+
+        Ok(Some(JournalEntry::new(sequence, command)))
+    }
+
+    // Only the record a crash interrupted can be damaged, and nothing was
+    // written after it
+    fn damaged_record(&mut self) -> io::Result<Option<JournalEntry>> {
+        match read_complete(&mut self.reader, &mut [0; 1])? {
+            true => Err(invalid_data("journal record is damaged")),
+            false => Ok(None),
+        }
+    }
+}
+
+impl<R: Read> Iterator for JournalReader<R> {
+    type Item = io::Result<JournalEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let entry = self.read_entry().transpose();
+        self.finished = !matches!(entry, Some(Ok(_)));
+        entry
+    }
+}
+
+// Fills the buffer, returns false if the input ends first
+fn read_complete<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<bool> {
+    match reader.read_exact(buffer) {
+        Ok(()) => Ok(true),
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(error) => Err(error),
+    }
+}
@@ -1,2 +1,9 @@
-#![feature(btree_cursors)]
+#![cfg_attr(feature = "btree_cursors", feature(btree_cursors))]
+pub mod codec;
+pub mod journal;
 pub mod matching_engine;
+pub mod replay;
+pub mod replication;
+pub mod runtime;
+pub mod server;
+pub mod snapshot;
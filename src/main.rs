@@ -1,8 +1,183 @@
-use trade_match::matching_engine::market::*;
+use std::env;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+use trade_match::journal::{self, FsyncPolicy, Journal, JournalHeader};
+use trade_match::matching_engine::exchange::Exchange;
+use trade_match::matching_engine::market::MarketConfig;
+use trade_match::replication::{Backup, Primary};
+use trade_match::server::Server;
+use trade_match::snapshot::{self, SnapshotSchedule};
+
+const USAGE: &str = "usage: trade-match [--listen ADDRESS] [--symbols SYMBOL,...] \
+                     [--journal DIRECTORY] [--fsync always|never|COUNT] \
+                     [--snapshot-interval COUNT] [--replicate ADDRESS] [--backup-of ADDRESS]";
 
 fn main() {
-    // @TODO Implement TCP/IP request processing
+    let mut address = String::from("127.0.0.1:7000");
+    let mut symbols = String::from("AAPL");
+    let mut journal_directory: Option<PathBuf> = None;
+    let mut fsync_policy = FsyncPolicy::Always;
+    let mut snapshot_interval: Option<u64> = None;
+    let mut replication_address: Option<String> = None;
+    let mut primary_address: Option<String> = None;
+
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        let value = args.next();
+
+        match (arg.as_str(), value) {
+            ("--listen", Some(value)) => address = value,
+            ("--symbols", Some(value)) => symbols = value,
+            ("--journal", Some(value)) => journal_directory = Some(PathBuf::from(value)),
+            ("--fsync", Some(value)) if parse_fsync_policy(&value).is_some() => {
+                fsync_policy = parse_fsync_policy(&value).unwrap()
+            }
+            ("--snapshot-interval", Some(value)) if value.parse::<u64>().is_ok_and(|n| n > 0) => {
+                snapshot_interval = value.parse().ok()
+            }
+            ("--replicate", Some(value)) => replication_address = Some(value),
+            ("--backup-of", Some(value)) => primary_address = Some(value),
+            _ => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    let symbols: Vec<&str> = symbols
+        .split(',')
+        .filter(|symbol| !symbol.is_empty())
+        .collect();
+
+    let mut exchange = Exchange::new();
+
+    for symbol in symbols.iter() {
+        let market = match exchange.create_market(symbol, MarketConfig::default()) {
+            Ok(market) => market,
+            Err(error) => {
+                eprintln!("Failed to create market {}: {}", symbol, error);
+                process::exit(2);
+            }
+        };
+
+        // one journal file per symbol, the state of a previous run is
+        // recovered from the latest snapshot and the journal after it
+        if let Some(directory) = &journal_directory {
+            let path = journal::path(directory, symbol);
+
+            match snapshot::recover(market, directory, &path).and_then(|_| {
+                Journal::open(&path, fsync_policy, &JournalHeader::from_market(market))
+            }) {
+                Ok(journal) => market.set_journal(journal),
+                Err(error) => {
+                    eprintln!(
+                        "Failed to recover {} from {}: {}",
+                        symbol,
+                        path.display(),
+                        error
+                    );
+                    process::exit(1);
+                }
+            }
+
+            if let Some(interval) = snapshot_interval {
+                market.set_snapshot_schedule(SnapshotSchedule::new(directory, interval));
+            }
+        }
+    }
+
+    // a backup only serves clients once the primary is gone
+    if let Some(primary_address) = primary_address {
+        let mut backup = match Backup::connect(&primary_address, exchange) {
+            Ok(backup) => backup,
+            Err(error) => {
+                eprintln!(
+                    "Failed to connect to the primary {}: {}",
+                    primary_address, error
+                );
+                process::exit(1);
+            }
+        };
+
+        println!("Following the primary at {}", primary_address);
+
+        match backup.run() {
+            Ok(()) => println!("Lost the primary, taking over"),
+            Err(error) => println!("Lost the primary ({}), taking over", error),
+        }
+
+        exchange = backup.promote();
+    }
+
+    // backups catch up from the journal, so replication needs one
+    if let Some(replication_address) = replication_address {
+        let directory = match &journal_directory {
+            Some(directory) => directory,
+            None => {
+                eprintln!("--replicate requires --journal");
+                process::exit(2);
+            }
+        };
+
+        let primary = match Primary::bind(&replication_address, directory, &exchange) {
+            Ok(primary) => primary,
+            Err(error) => {
+                eprintln!(
+                    "Failed to listen for a backup on {}: {}",
+                    replication_address, error
+                );
+                process::exit(1);
+            }
+        };
+
+        for symbol in symbols.iter() {
+            if let Some(market) = exchange.market_mut(symbol) {
+                market.set_publisher(primary.publisher());
+            }
+        }
+
+        println!(
+            "Replicating to a backup on {}",
+            primary.local_addr().unwrap()
+        );
+
+        thread::spawn(move || {
+            if let Err(error) = primary.run() {
+                eprintln!("Replication stopped: {}", error);
+            }
+        });
+    }
+
+    let server = match Server::bind(&address, exchange) {
+        Ok(server) => server,
+        Err(error) => {
+            eprintln!("Failed to listen on {}: {}", address, error);
+            process::exit(1);
+        }
+    };
+
+    println!(
+        "Listening on {} for the symbols {:?}",
+        server.local_addr().unwrap(),
+        symbols
+    );
+
+    if let Err(error) = server.run() {
+        eprintln!("Server stopped: {}", error);
+        process::exit(1);
+    }
+}
 
-    let market = Market::new("AAPL");
-    println!("Created a new market for the symbol {:?}", market.symbol());
+fn parse_fsync_policy(value: &str) -> Option<FsyncPolicy> {
+    match value {
+        "always" => Some(FsyncPolicy::Always),
+        "never" => Some(FsyncPolicy::Never),
+        count => count
+            .parse()
+            .ok()
+            .filter(|count| *count > 0)
+            .map(FsyncPolicy::Every),
+    }
 }
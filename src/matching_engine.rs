@@ -1,4 +1,16 @@
+pub mod command;
+pub mod depth;
+pub mod error;
+pub mod event;
+pub mod exchange;
+pub mod execution;
+pub mod ladder_book;
 pub mod market;
 pub mod order;
+pub mod order_book;
+pub mod pool;
+pub mod price;
 pub mod price_level;
 pub mod price_level_key;
+pub mod quantity;
+pub mod stop_book;
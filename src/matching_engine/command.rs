@@ -0,0 +1,202 @@
+use super::execution::ExecutionReport;
+use super::order::{Order, OrderRequest, OrderSide, OrderType, Owner, PostOnly, TimeInForce};
+use super::price::Price;
+use super::quantity::Quantity;
+use crate::codec::{invalid_data, Decoder, Encoder};
+use std::io;
+
+// An instruction that can be applied to a market
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    NewOrder(OrderRequest),
+    Cancel {
+        order_id: u64,
+    },
+    Replace {
+        order_id: u64,
+        price: Price,
+        quantity: Quantity,
+    },
+    // Stops the market accepting new orders, cancels are still accepted
+    Halt,
+    Resume,
+}
+
+#[derive(Debug)]
+pub enum CommandResult {
+    Executed(ExecutionReport),
+    Cancelled(Order),
+    Halted,
+    Resumed,
+}
+
+impl Command {
+    pub fn encode(&self, encoder: &mut Encoder) {
+        match *self {
+            Command::NewOrder(request) => {
+                encoder.u8(1);
+                encode_order_request(&request, encoder);
+            }
+            Command::Cancel { order_id } => {
+                encoder.u8(2).u64(order_id);
+            }
+            Command::Replace {
+                order_id,
+                price,
+                quantity,
+            } => {
+                encoder
+                    .u8(3)
+                    .u64(order_id)
+                    .i64(price.ticks())
+                    .u64(quantity.lots());
+            }
+            Command::Halt => {
+                encoder.u8(4);
+            }
+            Command::Resume => {
+                encoder.u8(5);
+            }
+        }
+    }
+
+    pub fn decode(decoder: &mut Decoder) -> io::Result<Command> {
+        match decoder.u8()? {
+            1 => Ok(Command::NewOrder(decode_order_request(decoder)?)),
+            2 => Ok(Command::Cancel {
+                order_id: decoder.u64()?,
+            }),
+            3 => Ok(Command::Replace {
+                order_id: decoder.u64()?,
+                price: Price::from_ticks(decoder.i64()?),
+                quantity: Quantity::from_lots(decoder.u64()?),
+            }),
+            4 => Ok(Command::Halt),
+            5 => Ok(Command::Resume),
+            _ => Err(invalid_data("unknown command")),
+        }
+    }
+}
+
+pub fn encode_side(side: OrderSide) -> u8 {
+    match side {
+        OrderSide::Bid => 0,
+        OrderSide::Ask => 1,
+    }
+}
+
+pub fn decode_side(value: u8) -> io::Result<OrderSide> {
+    match value {
+        0 => Ok(OrderSide::Bid),
+        1 => Ok(OrderSide::Ask),
+        _ => Err(invalid_data("unknown order side")),
+    }
+}
+
+pub fn encode_time_in_force(time_in_force: TimeInForce) -> u8 {
+    match time_in_force {
+        TimeInForce::GoodTillCancel => 0,
+        TimeInForce::ImmediateOrCancel => 1,
+        TimeInForce::FillOrKill => 2,
+    }
+}
+
+pub fn decode_time_in_force(value: u8) -> io::Result<TimeInForce> {
+    match value {
+        0 => Ok(TimeInForce::GoodTillCancel),
+        1 => Ok(TimeInForce::ImmediateOrCancel),
+        2 => Ok(TimeInForce::FillOrKill),
+        _ => Err(invalid_data("unknown time in force")),
+    }
+}
+
+pub fn encode_post_only(post_only: Option<PostOnly>) -> u8 {
+    match post_only {
+        None => 0,
+        Some(PostOnly::Reject) => 1,
+        Some(PostOnly::Slide) => 2,
+    }
+}
+
+pub fn decode_post_only(value: u8) -> io::Result<Option<PostOnly>> {
+    match value {
+        0 => Ok(None),
+        1 => Ok(Some(PostOnly::Reject)),
+        2 => Ok(Some(PostOnly::Slide)),
+        _ => Err(invalid_data("unknown post-only behaviour")),
+    }
+}
+
+pub fn encode_order_request(request: &OrderRequest, encoder: &mut Encoder) {
+    let order_type = match request.order_type() {
+        OrderType::Limit => 0,
+        OrderType::Market => 1,
+        OrderType::StopMarket => 2,
+        OrderType::StopLimit => 3,
+    };
+
+    encoder
+        .u8(encode_side(request.side()))
+        .u8(order_type)
+        .i64(request.price().map_or(0, |price| price.ticks()))
+        .i64(request.stop_price().map_or(0, |price| price.ticks()))
+        .u64(request.quantity().lots())
+        .u64(
+            request
+                .display_quantity()
+                .map_or(0, |quantity| quantity.lots()),
+        )
+        .u8(encode_time_in_force(request.time_in_force()))
+        .u8(encode_post_only(request.post_only()));
+
+    encode_owner(request.owner(), encoder);
+}
+
+// Trader ids start at 1, 0 is written for an owner without one
+pub fn encode_owner(owner: Owner, encoder: &mut Encoder) {
+    encoder
+        .u64(owner.account())
+        .u64(owner.trader().unwrap_or(0));
+}
+
+pub fn decode_owner(decoder: &mut Decoder) -> io::Result<Owner> {
+    let owner = Owner::new(decoder.u64()?);
+
+    Ok(match decoder.u64()? {
+        0 => owner,
+        trader => owner.with_trader(trader),
+    })
+}
+
+pub fn decode_order_request(decoder: &mut Decoder) -> io::Result<OrderRequest> {
+    let side = decode_side(decoder.u8()?)?;
+    let order_type = decoder.u8()?;
+    let price = Price::from_ticks(decoder.i64()?);
+    let stop_price = Price::from_ticks(decoder.i64()?);
+    let quantity = Quantity::from_lots(decoder.u64()?);
+    let display_quantity = Quantity::from_lots(decoder.u64()?);
+
+    let time_in_force = decode_time_in_force(decoder.u8()?)?;
+    let post_only = decode_post_only(decoder.u8()?)?;
+
+    let request = match order_type {
+        0 => OrderRequest::limit(side, price, quantity),
+        1 => OrderRequest::market(side, quantity),
+        2 => OrderRequest::stop_market(side, stop_price, quantity),
+        3 => OrderRequest::stop_limit(side, stop_price, price, quantity),
+        _ => return Err(invalid_data("unknown order type")),
+    };
+
+    let mut request = request
+        .with_time_in_force(time_in_force)
+        .with_owner(decode_owner(decoder)?);
+
+    if !display_quantity.is_zero() {
+        request = request.with_display_quantity(display_quantity);
+    }
+
+    Ok(match post_only {
+        Some(post_only) => request.with_post_only(post_only),
+        None => request,
+    })
+}
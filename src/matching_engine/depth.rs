@@ -0,0 +1,54 @@
+use super::price::Price;
+use super::quantity::Quantity;
+
+// Aggregated view of a single price level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepthLevel {
+    price: Price,
+    quantity: Quantity,
+    order_count: usize,
+}
+
+impl DepthLevel {
+    pub fn new(price: Price, quantity: Quantity, order_count: usize) -> Self {
+        DepthLevel {
+            price,
+            quantity,
+            order_count,
+        }
+    }
+
+    pub fn price(&self) -> Price {
+        self.price
+    }
+
+    // Displayed quantity, hidden iceberg reserve is not included
+    pub fn quantity(&self) -> Quantity {
+        self.quantity
+    }
+
+    pub fn order_count(&self) -> usize {
+        self.order_count
+    }
+}
+
+// Top of the book per side, best price first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Depth {
+    bids: Vec<DepthLevel>,
+    asks: Vec<DepthLevel>,
+}
+
+impl Depth {
+    pub fn new(bids: Vec<DepthLevel>, asks: Vec<DepthLevel>) -> Self {
+        Depth { bids, asks }
+    }
+
+    pub fn bids(&self) -> &[DepthLevel] {
+        &self.bids
+    }
+
+    pub fn asks(&self) -> &[DepthLevel] {
+        &self.asks
+    }
+}
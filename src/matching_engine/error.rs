@@ -0,0 +1,86 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarketError {
+    // Price is zero or negative
+    InvalidPrice,
+    // Price is not a multiple of the market's tick size
+    InvalidTick,
+    // Price lies outside the range of prices the book can hold
+    PriceOutOfRange,
+    // Quantity is not a multiple of the market's lot size
+    InvalidQuantity,
+    ZeroQuantity,
+    UnknownOrder(u64),
+    MarketHalted,
+    // Post-only order would have traded on entry
+    WouldTakeLiquidity,
+    SelfTrade,
+    RiskRejected,
+    // The command could not be written to the market's journal
+    JournalFailed,
+    // The market has issued every order id its counter can hold
+    OrderIdsExhausted,
+    // The price level cannot hold any more quantity
+    LevelFull,
+    // The market's tick or lot size is not positive
+    InvalidConfig,
+}
+
+impl fmt::Display for MarketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarketError::InvalidPrice => write!(f, "price must be positive"),
+            MarketError::InvalidTick => write!(f, "price is not a multiple of the tick size"),
+            MarketError::PriceOutOfRange => write!(f, "price is outside the market's price band"),
+            MarketError::InvalidQuantity => {
+                write!(f, "quantity is not a multiple of the lot size")
+            }
+            MarketError::ZeroQuantity => write!(f, "quantity must be greater than zero"),
+            MarketError::UnknownOrder(id) => write!(f, "unknown order {}", id),
+            MarketError::MarketHalted => write!(f, "market is halted"),
+            MarketError::WouldTakeLiquidity => {
+                write!(f, "post-only order would take liquidity")
+            }
+            MarketError::SelfTrade => write!(f, "order would trade against the same owner"),
+            MarketError::RiskRejected => write!(f, "order rejected by risk checks"),
+            MarketError::JournalFailed => write!(f, "command could not be journaled"),
+            MarketError::OrderIdsExhausted => write!(f, "no order ids left in the market"),
+            MarketError::LevelFull => write!(f, "price level is full"),
+            MarketError::InvalidConfig => write!(f, "tick and lot size must be positive"),
+        }
+    }
+}
+
+impl Error for MarketError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExchangeError {
+    UnknownSymbol(String),
+    DuplicateSymbol(String),
+    // All market ids have been handed out
+    TooManyMarkets,
+    Market(MarketError),
+}
+
+impl fmt::Display for ExchangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExchangeError::UnknownSymbol(symbol) => write!(f, "unknown symbol {}", symbol),
+            ExchangeError::DuplicateSymbol(symbol) => {
+                write!(f, "a market for {} already exists", symbol)
+            }
+            ExchangeError::TooManyMarkets => write!(f, "no market ids left"),
+            ExchangeError::Market(error) => error.fmt(f),
+        }
+    }
+}
+
+impl Error for ExchangeError {}
+
+impl From<MarketError> for ExchangeError {
+    fn from(error: MarketError) -> Self {
+        ExchangeError::Market(error)
+    }
+}
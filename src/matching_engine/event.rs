@@ -0,0 +1,99 @@
+use super::error::MarketError;
+use super::execution::Fill;
+use super::order::{OrderSide, Owner};
+use super::price::Price;
+use super::quantity::Quantity;
+use std::fmt;
+use std::sync::mpsc::Sender;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelReason {
+    // Cancelled on request of the order owner
+    Requested,
+    // Remainder of an order that could not be filled and is not allowed to rest
+    Unfilled,
+    // Quantity taken off an order to keep it from trading with its own account
+    SelfTrade,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarketEvent {
+    OrderAccepted {
+        order_id: u64,
+        side: OrderSide,
+        price: Option<Price>,
+        quantity: Quantity,
+        owner: Owner,
+    },
+    OrderPartiallyFilled {
+        order_id: u64,
+        fill: Fill,
+        remaining_quantity: Quantity,
+    },
+    OrderFilled {
+        order_id: u64,
+        fill: Fill,
+    },
+    OrderCancelled {
+        order_id: u64,
+        quantity: Quantity,
+        reason: CancelReason,
+    },
+    // Stop order activated by the last traded price and entered into the book
+    OrderTriggered {
+        order_id: u64,
+    },
+    OrderModified {
+        order_id: u64,
+        price: Price,
+        quantity: Quantity,
+    },
+    OrderRejected {
+        side: OrderSide,
+        reason: MarketError,
+    },
+    BookTopChanged {
+        best_bid: Option<Price>,
+        best_ask: Option<Price>,
+    },
+}
+
+pub trait MarketEventSink {
+    fn on_event(&mut self, event: &MarketEvent);
+}
+
+// Forwards events to a channel, events are dropped once the receiver hangs up
+impl MarketEventSink for Sender<MarketEvent> {
+    fn on_event(&mut self, event: &MarketEvent) {
+        let _ = self.send(event.clone());
+    }
+}
+
+#[derive(Default)]
+pub struct EventPublisher {
+    sinks: Vec<Box<dyn MarketEventSink + Send>>,
+}
+
+impl EventPublisher {
+    pub fn new() -> Self {
+        EventPublisher { sinks: Vec::new() }
+    }
+
+    pub fn subscribe(&mut self, sink: Box<dyn MarketEventSink + Send>) {
+        self.sinks.push(sink);
+    }
+
+    pub fn publish(&mut self, event: MarketEvent) {
+        for sink in self.sinks.iter_mut() {
+            sink.on_event(&event);
+        }
+    }
+}
+
+impl fmt::Debug for EventPublisher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventPublisher")
+            .field("sinks", &self.sinks.len())
+            .finish()
+    }
+}
@@ -0,0 +1,131 @@
+use super::command::*;
+use super::error::*;
+use super::execution::*;
+use super::market::*;
+use super::order::*;
+use super::price::*;
+use super::quantity::*;
+use std::collections::HashMap;
+
+// Owns the markets of every listed instrument and routes requests to them.
+// Each market gets its own id so order ids are unique across symbols, ids of
+// removed markets are not reused.
+#[derive(Debug, Default)]
+pub struct Exchange {
+    markets: HashMap<String, Market>,
+    // Symbol of each listed market by market id
+    symbols: HashMap<u16, String>,
+    total_markets: u16,
+}
+
+impl Exchange {
+    pub fn new() -> Self {
+        Exchange::default()
+    }
+
+    pub fn create_market(
+        &mut self,
+        symbol: &str,
+        config: MarketConfig,
+    ) -> Result<&mut Market, ExchangeError> {
+        if self.markets.contains_key(symbol) {
+            return Err(ExchangeError::DuplicateSymbol(symbol.to_string()));
+        }
+
+        let market_id = self
+            .total_markets
+            .checked_add(1)
+            .ok_or(ExchangeError::TooManyMarkets)?;
+        let market = Market::with_market_id(market_id, symbol, config)?;
+
+        self.total_markets = market_id;
+        self.symbols.insert(market_id, symbol.to_string());
+
+        Ok(self.markets.entry(symbol.to_string()).or_insert(market))
+    }
+
+    // Delists a market, its resting orders are dropped along with it
+    pub fn remove_market(&mut self, symbol: &str) -> Option<Market> {
+        let market = self.markets.remove(symbol)?;
+        self.symbols.remove(&market.market_id());
+        Some(market)
+    }
+
+    pub fn market(&self, symbol: &str) -> Option<&Market> {
+        self.markets.get(symbol)
+    }
+
+    pub fn market_mut(&mut self, symbol: &str) -> Option<&mut Market> {
+        self.markets.get_mut(symbol)
+    }
+
+    // Markets in the order they were created
+    pub fn into_markets(self) -> Vec<Market> {
+        let mut markets: Vec<Market> = self.markets.into_values().collect();
+        markets.sort_by_key(|market| market.market_id());
+        markets
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = &str> {
+        self.markets.keys().map(|symbol| symbol.as_str())
+    }
+
+    // Symbol of the market that issued an order id
+    pub fn symbol_of(&self, order_id: u64) -> Option<&str> {
+        let market_id = (order_id >> MARKET_ID_SHIFT) as u16;
+        self.symbols.get(&market_id).map(|symbol| symbol.as_str())
+    }
+
+    pub fn order_exists(&self, order_id: u64) -> bool {
+        self.symbol_of(order_id)
+            .and_then(|symbol| self.markets.get(symbol))
+            .is_some_and(|market| market.order_exists(order_id))
+    }
+
+    pub fn add_order(
+        &mut self,
+        symbol: &str,
+        request: OrderRequest,
+    ) -> Result<ExecutionReport, ExchangeError> {
+        Ok(self.route(symbol)?.add_order(request)?)
+    }
+
+    pub fn cancel_order(&mut self, order_id: u64) -> Result<Order, ExchangeError> {
+        Ok(self.route_order(order_id)?.cancel_limit_order(order_id)?)
+    }
+
+    pub fn modify_order(
+        &mut self,
+        order_id: u64,
+        price: Price,
+        quantity: Quantity,
+    ) -> Result<ExecutionReport, ExchangeError> {
+        Ok(self
+            .route_order(order_id)?
+            .modify_order(order_id, price, quantity)?)
+    }
+
+    pub fn apply(
+        &mut self,
+        symbol: &str,
+        command: Command,
+    ) -> Result<CommandResult, ExchangeError> {
+        Ok(self.route(symbol)?.apply(command)?)
+    }
+
+    fn route(&mut self, symbol: &str) -> Result<&mut Market, ExchangeError> {
+        self.markets
+            .get_mut(symbol)
+            .ok_or_else(|| ExchangeError::UnknownSymbol(symbol.to_string()))
+    }
+
+    // Orders of unknown or delisted markets are reported as unknown orders
+    fn route_order(&mut self, order_id: u64) -> Result<&mut Market, ExchangeError> {
+        let market_id = (order_id >> MARKET_ID_SHIFT) as u16;
+
+        self.symbols
+            .get(&market_id)
+            .and_then(|symbol| self.markets.get_mut(symbol))
+            .ok_or(ExchangeError::Market(MarketError::UnknownOrder(order_id)))
+    }
+}
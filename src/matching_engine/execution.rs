@@ -0,0 +1,222 @@
+use super::order::{OrderSide, Owner};
+use super::price::Price;
+use super::quantity::Quantity;
+
+// A single trade between an incoming (aggressor) order and a resting (passive) order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fill {
+    trade_id: u64,
+    aggressor_id: u64,
+    passive_id: u64,
+    price: Price,
+    quantity: Quantity,
+    aggressor_side: OrderSide,
+    aggressor_owner: Owner,
+    passive_owner: Owner,
+}
+
+impl Fill {
+    pub fn new(
+        trade_id: u64,
+        aggressor_id: u64,
+        passive_id: u64,
+        price: Price,
+        quantity: Quantity,
+        aggressor_side: OrderSide,
+    ) -> Self {
+        Fill {
+            trade_id,
+            aggressor_id,
+            passive_id,
+            price,
+            quantity,
+            aggressor_side,
+            aggressor_owner: Owner::default(),
+            passive_owner: Owner::default(),
+        }
+    }
+
+    pub fn with_owners(mut self, aggressor_owner: Owner, passive_owner: Owner) -> Self {
+        self.aggressor_owner = aggressor_owner;
+        self.passive_owner = passive_owner;
+        self
+    }
+
+    pub fn trade_id(&self) -> u64 {
+        self.trade_id
+    }
+
+    pub fn aggressor_id(&self) -> u64 {
+        self.aggressor_id
+    }
+
+    pub fn passive_id(&self) -> u64 {
+        self.passive_id
+    }
+
+    pub fn price(&self) -> Price {
+        self.price
+    }
+
+    pub fn quantity(&self) -> Quantity {
+        self.quantity
+    }
+
+    pub fn aggressor_side(&self) -> OrderSide {
+        self.aggressor_side
+    }
+
+    pub fn aggressor_owner(&self) -> Owner {
+        self.aggressor_owner
+    }
+
+    pub fn passive_owner(&self) -> Owner {
+        self.passive_owner
+    }
+}
+
+// The part of an incoming order left on the book after matching
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestingOrder {
+    id: u64,
+    price: Price,
+    quantity: Quantity,
+}
+
+impl RestingOrder {
+    pub fn new(id: u64, price: Price, quantity: Quantity) -> Self {
+        RestingOrder {
+            id,
+            price,
+            quantity,
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn price(&self) -> Price {
+        self.price
+    }
+
+    pub fn quantity(&self) -> Quantity {
+        self.quantity
+    }
+}
+
+// Outcome of submitting an order to a market
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionReport {
+    order_id: u64,
+    side: OrderSide,
+    owner: Owner,
+    fills: Vec<Fill>,
+    resting: Option<RestingOrder>,
+    cancelled_quantity: Quantity,
+    // Part of the cancelled quantity taken off by self-trade prevention
+    self_traded_quantity: Quantity,
+    // Resting orders cancelled or reduced by self-trade prevention, with the
+    // quantity taken off each
+    prevented: Vec<RestingOrder>,
+    // Stop order waiting in the trigger book
+    pending: bool,
+    // Reports for stop orders triggered by trades of this order, in trigger order
+    triggered: Vec<ExecutionReport>,
+}
+
+impl ExecutionReport {
+    pub fn new(
+        order_id: u64,
+        side: OrderSide,
+        fills: Vec<Fill>,
+        resting: Option<RestingOrder>,
+        cancelled_quantity: Quantity,
+    ) -> Self {
+        ExecutionReport {
+            order_id,
+            side,
+            owner: Owner::default(),
+            fills,
+            resting,
+            cancelled_quantity,
+            self_traded_quantity: Quantity::ZERO,
+            prevented: Vec::new(),
+            pending: false,
+            triggered: Vec::new(),
+        }
+    }
+
+    pub fn pending(order_id: u64, side: OrderSide) -> Self {
+        ExecutionReport {
+            pending: true,
+            ..ExecutionReport::new(order_id, side, Vec::new(), None, Quantity::ZERO)
+        }
+    }
+
+    pub fn with_owner(mut self, owner: Owner) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    // Adds what self-trade prevention took off this order to its cancelled quantity
+    pub fn with_self_trades(mut self, quantity: Quantity, prevented: Vec<RestingOrder>) -> Self {
+        self.cancelled_quantity += quantity;
+        self.self_traded_quantity = quantity;
+        self.prevented = prevented;
+        self
+    }
+
+    pub fn order_id(&self) -> u64 {
+        self.order_id
+    }
+
+    pub fn side(&self) -> OrderSide {
+        self.side
+    }
+
+    pub fn owner(&self) -> Owner {
+        self.owner
+    }
+
+    pub fn fills(&self) -> &[Fill] {
+        &self.fills
+    }
+
+    pub fn resting(&self) -> Option<&RestingOrder> {
+        self.resting.as_ref()
+    }
+
+    // Quantity that was neither filled nor left on the book
+    pub fn cancelled_quantity(&self) -> Quantity {
+        self.cancelled_quantity
+    }
+
+    pub fn self_traded_quantity(&self) -> Quantity {
+        self.self_traded_quantity
+    }
+
+    pub fn prevented(&self) -> &[RestingOrder] {
+        &self.prevented
+    }
+
+    pub fn filled_quantity(&self) -> Quantity {
+        self.fills.iter().map(|fill| fill.quantity()).sum()
+    }
+
+    pub fn is_filled(&self) -> bool {
+        self.resting.is_none() && self.cancelled_quantity.is_zero() && !self.pending
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    pub fn triggered(&self) -> &[ExecutionReport] {
+        &self.triggered
+    }
+
+    pub fn add_triggered(&mut self, report: ExecutionReport) {
+        self.triggered.push(report);
+    }
+}
@@ -0,0 +1,234 @@
+use super::order::OrderSide;
+use super::order_book::OrderBook;
+use super::price::Price;
+use super::price_level::PriceLevel;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+// Levels kept in an array indexed by tick offset from the lowest price of a
+// fixed price band. A bitmap of non-empty levels finds the best price without
+// visiting the empty levels in between.
+#[derive(Debug)]
+pub struct LadderBook {
+    min_price: Price,
+    max_price: Price,
+    tick_size: Price,
+    bids: Ladder,
+    asks: Ladder,
+}
+
+impl LadderBook {
+    // Holds every tick from `min_price` to `max_price` inclusive, both of
+    // which must be multiples of the tick size
+    pub fn new(min_price: Price, max_price: Price, tick_size: Price) -> Self {
+        assert!(tick_size.is_positive(), "tick size must be positive");
+        assert!(min_price <= max_price, "empty price band");
+        assert!(
+            min_price.ticks() % tick_size.ticks() == 0
+                && max_price.ticks() % tick_size.ticks() == 0,
+            "price band is not aligned to the tick size"
+        );
+
+        let ticks = ((max_price.ticks() - min_price.ticks()) / tick_size.ticks()) as usize + 1;
+        let price_at =
+            |index: usize| Price::from_ticks(min_price.ticks() + index as i64 * tick_size.ticks());
+
+        LadderBook {
+            min_price,
+            max_price,
+            tick_size,
+            bids: Ladder::new(ticks, false, price_at),
+            asks: Ladder::new(ticks, true, price_at),
+        }
+    }
+
+    pub fn min_price(&self) -> Price {
+        self.min_price
+    }
+
+    pub fn max_price(&self) -> Price {
+        self.max_price
+    }
+
+    fn index(&self, price: Price) -> Option<usize> {
+        self.contains_price(price)
+            .then(|| ((price.ticks() - self.min_price.ticks()) / self.tick_size.ticks()) as usize)
+    }
+
+    fn ladder(&self, side: OrderSide) -> &Ladder {
+        match side {
+            OrderSide::Bid => &self.bids,
+            OrderSide::Ask => &self.asks,
+        }
+    }
+
+    fn ladder_mut(&mut self, side: OrderSide) -> &mut Ladder {
+        match side {
+            OrderSide::Bid => &mut self.bids,
+            OrderSide::Ask => &mut self.asks,
+        }
+    }
+}
+
+impl OrderBook for LadderBook {
+    fn best_price(&self, side: OrderSide) -> Option<Price> {
+        let ladder = self.ladder(side);
+
+        ladder.best.map(|index| ladder.levels[index].price())
+    }
+
+    fn level(&self, side: OrderSide, price: Price) -> Option<&PriceLevel> {
+        let index = self.index(price)?;
+        let ladder = self.ladder(side);
+
+        ladder.is_occupied(index).then(|| &ladder.levels[index])
+    }
+
+    fn level_mut(&mut self, side: OrderSide, price: Price) -> Option<&mut PriceLevel> {
+        let index = self.index(price)?;
+        let ladder = self.ladder_mut(side);
+
+        match ladder.is_occupied(index) {
+            true => Some(&mut ladder.levels[index]),
+            false => None,
+        }
+    }
+
+    // Panics if the price has no level in the ladder, callers check `contains_price` first
+    fn level_entry(&mut self, side: OrderSide, price: Price) -> &mut PriceLevel {
+        let index = self.index(price).expect("price outside the ladder");
+        let ladder = self.ladder_mut(side);
+
+        ladder.occupy(index);
+        &mut ladder.levels[index]
+    }
+
+    fn remove_level(&mut self, side: OrderSide, price: Price) {
+        if let Some(index) = self.index(price) {
+            let ladder = self.ladder_mut(side);
+
+            debug_assert_eq!(ladder.levels[index].order_count(), 0);
+            ladder.vacate(index);
+        }
+    }
+
+    fn levels(&self, side: OrderSide) -> impl Iterator<Item = &PriceLevel> + '_ {
+        let ladder = self.ladder(side);
+
+        std::iter::successors(ladder.best, move |&index| match side {
+            OrderSide::Bid => index
+                .checked_sub(1)
+                .and_then(|index| ladder.previous(index)),
+            OrderSide::Ask => ladder.next(index + 1),
+        })
+        .map(move |index| &ladder.levels[index])
+    }
+
+    fn level_count(&self, side: OrderSide) -> usize {
+        self.ladder(side).len
+    }
+
+    // Prices between the ladder's ticks have no level, even when the market's
+    // own tick size allows them
+    fn contains_price(&self, price: Price) -> bool {
+        price >= self.min_price
+            && price <= self.max_price
+            && (price.ticks() - self.min_price.ticks()) % self.tick_size.ticks() == 0
+    }
+}
+
+// One side of the book, a bit is set for every level holding orders
+#[derive(Debug)]
+struct Ladder {
+    levels: Vec<PriceLevel>,
+    occupied: Vec<u64>,
+    len: usize,
+    // Asks rank lower offsets first, bids higher ones
+    ascending: bool,
+    // Offset of the best level, the bitmap is only searched when it empties
+    best: Option<usize>,
+}
+
+impl Ladder {
+    fn new(ticks: usize, ascending: bool, price_at: impl Fn(usize) -> Price) -> Self {
+        Ladder {
+            levels: (0..ticks)
+                .map(|index| PriceLevel::new(price_at(index)))
+                .collect(),
+            occupied: vec![0; ticks.div_ceil(WORD_BITS)],
+            len: 0,
+            ascending,
+            best: None,
+        }
+    }
+
+    fn is_better(&self, index: usize, than: usize) -> bool {
+        match self.ascending {
+            true => index < than,
+            false => index > than,
+        }
+    }
+
+    fn is_occupied(&self, index: usize) -> bool {
+        self.occupied[index / WORD_BITS] & (1 << (index % WORD_BITS)) != 0
+    }
+
+    fn occupy(&mut self, index: usize) {
+        if !self.is_occupied(index) {
+            self.occupied[index / WORD_BITS] |= 1 << (index % WORD_BITS);
+            self.len += 1;
+
+            if self.best.is_none_or(|best| self.is_better(index, best)) {
+                self.best = Some(index);
+            }
+        }
+    }
+
+    fn vacate(&mut self, index: usize) {
+        if self.is_occupied(index) {
+            self.occupied[index / WORD_BITS] &= !(1 << (index % WORD_BITS));
+            self.len -= 1;
+
+            // the next best level can only be further away from the spread
+            if self.best == Some(index) {
+                self.best = match (self.len, self.ascending) {
+                    (0, _) => None,
+                    (_, true) => self.next(index),
+                    (_, false) => self.previous(index),
+                };
+            }
+        }
+    }
+
+    // First occupied index at or above `from`
+    fn next(&self, from: usize) -> Option<usize> {
+        let mut word_index = from / WORD_BITS;
+        let mut word = *self.occupied.get(word_index)? & (u64::MAX << (from % WORD_BITS));
+
+        loop {
+            if word != 0 {
+                return Some(word_index * WORD_BITS + word.trailing_zeros() as usize);
+            }
+
+            word_index += 1;
+            word = *self.occupied.get(word_index)?;
+        }
+    }
+
+    // Last occupied index at or below `from`
+    fn previous(&self, from: usize) -> Option<usize> {
+        let mut word_index = from / WORD_BITS;
+        let mut word = self.occupied[word_index] & (u64::MAX >> (WORD_BITS - 1 - from % WORD_BITS));
+
+        loop {
+            if word != 0 {
+                return Some(
+                    word_index * WORD_BITS + (WORD_BITS - 1 - word.leading_zeros() as usize),
+                );
+            }
+
+            word_index = word_index.checked_sub(1)?;
+            word = self.occupied[word_index];
+        }
+    }
+}
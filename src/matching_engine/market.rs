@@ -1,336 +1,1162 @@
+use super::command::*;
+use super::depth::*;
+use super::error::*;
+use super::event::*;
+use super::execution::*;
 use super::order::*;
+use super::order_book::*;
+use super::pool::*;
+use super::price::*;
 use super::price_level::*;
-use super::price_level_key::*;
-use std::collections::BTreeMap;
+use super::quantity::*;
+use super::stop_book::*;
+use crate::journal::Journal;
+use crate::replication::Publisher;
+use crate::snapshot::{MarketSnapshot, SnapshotSchedule};
 use std::collections::HashMap;
-use std::ops::Bound::Included;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarketConfig {
+    // Minimum price increment, prices must be a multiple of it
+    pub tick_size: Price,
+    // Minimum quantity increment, quantities must be a multiple of it
+    pub lot_size: Quantity,
+    // Resting orders the market has room for before it needs to allocate
+    pub order_capacity: usize,
+    // Applied when orders of the same account would trade, None lets them trade
+    pub self_trade_prevention: Option<SelfTradePrevention>,
+}
+
+impl Default for MarketConfig {
+    fn default() -> Self {
+        MarketConfig {
+            tick_size: Price::from_ticks(1),
+            lot_size: Quantity::from_lots(1),
+            order_capacity: 1024,
+            self_trade_prevention: None,
+        }
+    }
+}
+
+impl MarketConfig {
+    // Prices and quantities must be multiples of the tick and lot size, so
+    // both have to be positive
+    pub fn validate(&self) -> Result<(), MarketError> {
+        match self.tick_size.is_positive() && !self.lot_size.is_zero() {
+            true => Ok(()),
+            false => Err(MarketError::InvalidConfig),
+        }
+    }
+}
+
+// Order ids carry the id of the market that issued them in their top bits,
+// leaving the remaining bits for the per-market order counter
+pub const MARKET_ID_SHIFT: u32 = 48;
+
+// Highest order counter a market reaches before it runs out of order ids
+const MAX_ORDER_COUNTER: u64 = (1 << MARKET_ID_SHIFT) - 1;
+
+// An incoming order being matched against the book and what matching has
+// done to it so far
+struct Matching {
+    id: u64,
+    owner: Owner,
+    self_trade_prevention: Option<SelfTradePrevention>,
+    fills: Vec<Fill>,
+    // Resting orders cancelled or reduced by self-trade prevention
+    prevented: Vec<RestingOrder>,
+    // Quantity of the incoming order cancelled by self-trade prevention
+    self_traded_quantity: Quantity,
+}
+
+impl Matching {
+    fn new(id: u64, owner: Owner, self_trade_prevention: Option<SelfTradePrevention>) -> Self {
+        Matching {
+            id,
+            owner,
+            self_trade_prevention,
+            fills: Vec::new(),
+            prevented: Vec::new(),
+            self_traded_quantity: Quantity::ZERO,
+        }
+    }
+
+    // Prevention to apply before trading with a resting order of this owner
+    fn self_trade_prevention(&self, passive_owner: &Owner) -> Option<SelfTradePrevention> {
+        self.self_trade_prevention
+            .filter(|_| self.owner.same_participant(passive_owner))
+    }
+}
 
 #[derive(Debug)]
-pub struct Market<'a> {
-    symbol: &'a str,
+pub struct Market<B: OrderBook = TreeBook> {
+    symbol: String,
+    market_id: u16,
+    config: MarketConfig,
     total_orders: u64,
-    lowest_ask: f32,
-    highest_bid: f32,
-    ask_levels: BTreeMap<PriceLevelKeyAsk, PriceLevel>,
-    bid_levels: BTreeMap<PriceLevelKeyBid, PriceLevel>,
-    orders: HashMap<u64, (OrderSide, f32)>,
+    total_trades: u64,
+    total_sequence: u64,
+    halted: bool,
+    last_trade_price: Option<Price>,
+    book: B,
+    // Side, price and slab key of every resting order
+    orders: HashMap<u64, (OrderSide, Price, usize)>,
+    order_pool: Slab<Order>,
+    stop_book: StopBook,
+    events: EventPublisher,
+    // Commands are written here before they are applied
+    journal: Option<Journal>,
+    snapshot_schedule: Option<SnapshotSchedule>,
+    // Journaled commands are passed on to a backup through this
+    publisher: Option<Publisher>,
+}
+
+impl Market {
+    pub fn new(symbol: impl Into<String>) -> Self {
+        Market::with_config(symbol, MarketConfig::default()).expect("default config is valid")
+    }
+
+    pub fn with_config(
+        symbol: impl Into<String>,
+        config: MarketConfig,
+    ) -> Result<Self, MarketError> {
+        Market::with_market_id(0, symbol, config)
+    }
+
+    // Markets with different ids never issue the same order id
+    pub fn with_market_id(
+        market_id: u16,
+        symbol: impl Into<String>,
+        config: MarketConfig,
+    ) -> Result<Self, MarketError> {
+        Market::with_book(market_id, symbol, config, TreeBook::new())
+    }
 }
 
-impl<'a> Market<'a> {
-    pub fn new(symbol: &'a str) -> Self {
-        Market {
-            symbol,
+impl<B: OrderBook> Market<B> {
+    // Prices outside of what the book can hold are rejected
+    pub fn with_book(
+        market_id: u16,
+        symbol: impl Into<String>,
+        config: MarketConfig,
+        book: B,
+    ) -> Result<Self, MarketError> {
+        config.validate()?;
+
+        Ok(Market {
+            symbol: symbol.into(),
+            market_id,
+            config,
             total_orders: 0,
-            lowest_ask: f32::INFINITY,
-            highest_bid: f32::NEG_INFINITY,
-            ask_levels: BTreeMap::new(),
-            bid_levels: BTreeMap::new(),
-            orders: HashMap::new(),
-        }
+            total_trades: 0,
+            total_sequence: 0,
+            halted: false,
+            last_trade_price: None,
+            book,
+            orders: HashMap::with_capacity(config.order_capacity),
+            order_pool: Slab::with_capacity(config.order_capacity),
+            stop_book: StopBook::new(),
+            events: EventPublisher::new(),
+            journal: None,
+            snapshot_schedule: None,
+            publisher: None,
+        })
     }
 
     pub fn symbol(&self) -> &str {
-        self.symbol
+        &self.symbol
     }
 
-    pub fn best_bid(&self) -> f32 {
-        self.highest_bid
+    pub fn market_id(&self) -> u16 {
+        self.market_id
     }
 
-    pub fn best_ask(&self) -> f32 {
-        self.lowest_ask
+    pub fn config(&self) -> &MarketConfig {
+        &self.config
     }
 
-    pub fn order_exists(&self, order_id: u64) -> bool {
-        self.orders.contains_key(&order_id)
+    pub fn book(&self) -> &B {
+        &self.book
+    }
+
+    pub fn best_bid(&self) -> Option<Price> {
+        self.book.best_price(OrderSide::Bid)
+    }
+
+    pub fn best_ask(&self) -> Option<Price> {
+        self.book.best_price(OrderSide::Ask)
+    }
+
+    // Displayed quantity resting at a price, hidden iceberg reserve is not included
+    pub fn level_quantity(&self, side: OrderSide, price: Price) -> Quantity {
+        self.book
+            .level(side, price)
+            .map_or(Quantity::ZERO, |level| level.quantity())
+    }
+
+    // Number of non-empty price levels on one side of the book
+    pub fn level_count(&self, side: OrderSide) -> usize {
+        self.book.level_count(side)
+    }
+
+    // Orders resting at a price in queue order
+    pub fn level_orders(
+        &self,
+        side: OrderSide,
+        price: Price,
+    ) -> impl Iterator<Item = OrderView> + '_ {
+        self.book
+            .level(side, price)
+            .into_iter()
+            .flat_map(move |level| level.orders(&self.order_pool))
+            .map(move |order| OrderView::new(side, price, order))
+    }
+
+    // Looks up an order resting on the book, pending stop orders are not included
+    pub fn get_order(&self, order_id: u64) -> Option<OrderView> {
+        let (side, price, key) = *self.orders.get(&order_id)?;
+
+        Some(OrderView::new(side, price, &self.order_pool[key]))
     }
 
-    pub fn add_market_bid(&mut self, quantity: f32) -> (bool, f32) {
-        if self.lowest_ask == f32::INFINITY {
-            return (false, quantity);
+    // Owner of a resting or pending stop order
+    pub fn order_owner(&self, order_id: u64) -> Option<Owner> {
+        match self.get_order(order_id) {
+            Some(order) => Some(order.owner()),
+            None => self
+                .stop_book
+                .get(order_id)
+                .map(|stop| stop.request().owner()),
         }
+    }
+
+    // Up to `levels` price levels per side, best price first
+    pub fn depth(&self, levels: usize) -> Depth {
+        Depth::new(
+            self.bid_depth().take(levels).collect(),
+            self.ask_depth().take(levels).collect(),
+        )
+    }
+
+    // All bid levels from the highest price down
+    pub fn bid_depth(&self) -> impl Iterator<Item = DepthLevel> + '_ {
+        Self::depth_levels(self.book.levels(OrderSide::Bid))
+    }
+
+    // All ask levels from the lowest price up
+    pub fn ask_depth(&self) -> impl Iterator<Item = DepthLevel> + '_ {
+        Self::depth_levels(self.book.levels(OrderSide::Ask))
+    }
+
+    // Price of the most recent trade, stop orders trigger off this price
+    pub fn last_trade_price(&self) -> Option<Price> {
+        self.last_trade_price
+    }
 
-        let quantity_remaining = self.execute_bid(None, quantity);
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
 
-        if quantity_remaining == 0.0 {
-            return (true, 0.0);
-        } else {
-            return (false, quantity_remaining);
+    // Rejects new orders until `resume` is called, cancels are still accepted
+    pub fn halt(&mut self) -> Result<(), MarketError> {
+        self.record(Command::Halt)?;
+        self.halted = true;
+        Ok(())
+    }
+
+    pub fn resume(&mut self) -> Result<(), MarketError> {
+        self.record(Command::Resume)?;
+        self.halted = false;
+        Ok(())
+    }
+
+    pub fn subscribe(&mut self, sink: Box<dyn MarketEventSink + Send>) {
+        self.events.subscribe(sink);
+    }
+
+    // Journals every add, cancel, modify, halt and resume from now on. Replaying
+    // the journal into an empty market with the same id and config rebuilds the book.
+    pub fn set_journal(&mut self, journal: Journal) {
+        self.journal = Some(journal);
+    }
+
+    pub fn journal(&self) -> Option<&Journal> {
+        self.journal.as_ref()
+    }
+
+    pub fn take_journal(&mut self) -> Option<Journal> {
+        self.journal.take()
+    }
+
+    // Snapshots a journaled market as it reaches the schedule's interval
+    pub fn set_snapshot_schedule(&mut self, schedule: SnapshotSchedule) {
+        self.snapshot_schedule = Some(schedule);
+    }
+
+    // Replicates a journaled market, commands are published once journaled
+    pub fn set_publisher(&mut self, publisher: Publisher) {
+        self.publisher = Some(publisher);
+    }
+
+    // State of the market after the last journaled command
+    pub fn snapshot(&self) -> MarketSnapshot {
+        let orders = [OrderSide::Bid, OrderSide::Ask]
+            .into_iter()
+            .flat_map(|side| {
+                self.book.levels(side).flat_map(move |level| {
+                    level
+                        .orders(&self.order_pool)
+                        .map(move |order| OrderView::new(side, level.price(), order))
+                })
+            })
+            .collect();
+
+        MarketSnapshot {
+            sequence: self
+                .journal
+                .as_ref()
+                .map_or(0, |journal| journal.sequence()),
+            symbol: self.symbol.clone(),
+            market_id: self.market_id,
+            config: self.config,
+            total_orders: self.total_orders,
+            total_trades: self.total_trades,
+            total_sequence: self.total_sequence,
+            halted: self.halted,
+            last_trade_price: self.last_trade_price,
+            orders,
+            stops: self.stop_orders().copied().collect(),
         }
     }
 
-    pub fn add_market_ask(&mut self, quantity: f32) -> (bool, f32) {
-        if self.highest_bid == f32::NEG_INFINITY {
-            return (false, quantity);
+    // Loads a snapshot of this market into it before any orders were added,
+    // events are not published for the restored orders
+    pub fn restore(&mut self, snapshot: &MarketSnapshot) -> Result<(), MarketError> {
+        debug_assert!(self.orders.is_empty() && self.stop_book.is_empty());
+        debug_assert!(snapshot.symbol == self.symbol && snapshot.market_id == self.market_id);
+
+        if snapshot
+            .orders
+            .iter()
+            .any(|order| !self.book.contains_price(order.price()))
+        {
+            return Err(MarketError::PriceOutOfRange);
         }
 
-        let quantity_remaining = self.execute_ask(None, quantity);
+        snapshot.config.validate()?;
+
+        self.config = snapshot.config;
+        self.total_orders = snapshot.total_orders;
+        self.total_trades = snapshot.total_trades;
+        self.total_sequence = snapshot.total_sequence;
+        self.halted = snapshot.halted;
+        self.last_trade_price = snapshot.last_trade_price;
+
+        for view in snapshot.orders.iter() {
+            let key = self.order_pool.insert(Order::from_view(view));
+
+            self.book
+                .level_entry(view.side(), view.price())
+                .add_order(&mut self.order_pool, key);
 
-        if quantity_remaining == 0.0 {
-            return (true, 0.0);
-        } else {
-            return (false, quantity_remaining);
+            self.orders
+                .insert(view.id(), (view.side(), view.price(), key));
         }
+
+        for stop in snapshot.stops.iter() {
+            self.stop_book.insert(*stop);
+        }
+
+        Ok(())
+    }
+
+    // Stop orders waiting for their stop price, buy stops first
+    pub fn stop_orders(&self) -> impl Iterator<Item = &StopOrder> + '_ {
+        self.stop_book.orders()
+    }
+
+    // Includes stop orders that have not been triggered yet
+    pub fn order_exists(&self, order_id: u64) -> bool {
+        self.orders.contains_key(&order_id) || self.stop_book.contains(order_id)
+    }
+
+    pub fn add_market_bid(&mut self, quantity: Quantity) -> Result<ExecutionReport, MarketError> {
+        self.add_order(OrderRequest::market(OrderSide::Bid, quantity))
     }
 
-    pub fn add_limit_bid(&mut self, price: f32, mut quantity: f32) -> Result<u64, &str> {
-        if !Market::check_precision(price) {
-            return Err("Price must be positive and cannot have more then 2 decimal places");
+    pub fn add_market_ask(&mut self, quantity: Quantity) -> Result<ExecutionReport, MarketError> {
+        self.add_order(OrderRequest::market(OrderSide::Ask, quantity))
+    }
+
+    pub fn add_limit_bid(
+        &mut self,
+        price: Price,
+        quantity: Quantity,
+    ) -> Result<ExecutionReport, MarketError> {
+        self.add_order(OrderRequest::limit(OrderSide::Bid, price, quantity))
+    }
+
+    pub fn add_limit_ask(
+        &mut self,
+        price: Price,
+        quantity: Quantity,
+    ) -> Result<ExecutionReport, MarketError> {
+        self.add_order(OrderRequest::limit(OrderSide::Ask, price, quantity))
+    }
+
+    // Stop orders are held until the last traded price reaches their stop
+    // price, a stop that is already reached on entry is triggered immediately
+    pub fn add_order(&mut self, request: OrderRequest) -> Result<ExecutionReport, MarketError> {
+        self.record(Command::NewOrder(request))?;
+
+        let side = request.side();
+        let quantity = request.quantity();
+
+        let price = self.validate_order(&request, None)?;
+
+        let id = self.increment_total_orders()?;
+        let book_top = self.book_top();
+
+        self.events.publish(MarketEvent::OrderAccepted {
+            order_id: id,
+            side,
+            price,
+            quantity,
+            owner: request.owner(),
+        });
+
+        let mut report = match request.stop_price() {
+            Some(stop_price) if !self.is_stop_triggered(side, stop_price) => {
+                self.stop_book.insert(StopOrder::new(id, request));
+                ExecutionReport::pending(id, side).with_owner(request.owner())
+            }
+            Some(_) => {
+                self.events
+                    .publish(MarketEvent::OrderTriggered { order_id: id });
+                self.execute_order(id, &request, price)
+            }
+            None => self.execute_order(id, &request, price),
+        };
+
+        self.trigger_stops(&mut report);
+        self.publish_book_top(book_top);
+
+        Ok(report)
+    }
+
+    // Cancels a resting order or a stop order that has not been triggered
+    pub fn cancel_limit_order(&mut self, id: u64) -> Result<Order, MarketError> {
+        self.record(Command::Cancel { order_id: id })?;
+
+        let book_top = self.book_top();
+
+        let order = match self.remove_order(id) {
+            Some((_, _, order)) => order,
+            None => match self.stop_book.remove(id) {
+                Some(stop) => {
+                    Order::new(id, 0, stop.request().quantity()).with_owner(stop.request().owner())
+                }
+                None => return Err(MarketError::UnknownOrder(id)),
+            },
+        };
+
+        self.events.publish(MarketEvent::OrderCancelled {
+            order_id: id,
+            quantity: order.quantity(),
+            reason: CancelReason::Requested,
+        });
+        self.publish_book_top(book_top);
+
+        Ok(order)
+    }
+
+    // Changes the price and/or quantity of a resting order. The order keeps its
+    // time priority only if the price is unchanged and the quantity is reduced,
+    // otherwise it is re-entered (and may trade) as if it were new. Either way
+    // it keeps its fill history, time in force and post-only instruction. A
+    // re-entered order's original quantity becomes what it has filled plus
+    // the new quantity.
+    pub fn modify_order(
+        &mut self,
+        id: u64,
+        price: Price,
+        quantity: Quantity,
+    ) -> Result<ExecutionReport, MarketError> {
+        self.record(Command::Replace {
+            order_id: id,
+            price,
+            quantity,
+        })?;
+
+        let (side, current_price, key) = match self.orders.get(&id) {
+            Some(entry) => *entry,
+            None => return Err(MarketError::UnknownOrder(id)),
+        };
+
+        let current = &self.order_pool[key];
+        let current_quantity = current.quantity();
+        let owner = current.owner();
+
+        let mut request = OrderRequest::limit(side, price, quantity)
+            .with_time_in_force(current.time_in_force())
+            .with_owner(owner);
+
+        if let Some(display_quantity) = current.display_quantity() {
+            request = request.with_display_quantity(display_quantity);
         }
 
-        // marketable order
-        if price >= self.lowest_ask {
-            quantity = self.execute_bid(Some(price), quantity);
+        if let Some(post_only) = current.post_only() {
+            request = request.with_post_only(post_only);
         }
 
-        if quantity <= 0.0 {
-            return Ok(0);
-        } else {
-            let id = self.increment_total_orders();
+        // a sliding post-only order may be moved off the requested price
+        let price = self
+            .validate_order(&request, Some((current_price, current_quantity)))?
+            .unwrap();
 
-            let order = Order::new(id, quantity);
+        let book_top = self.book_top();
 
-            self.highest_bid = f32::max(price, self.highest_bid);
+        self.events.publish(MarketEvent::OrderModified {
+            order_id: id,
+            price,
+            quantity,
+        });
 
-            let level_key = PriceLevelKeyBid::new(price);
+        let mut report = match price == current_price && quantity <= current_quantity {
+            true => {
+                let level = self.book.level_mut(side, current_price).unwrap();
+                level.reduce_order(&mut self.order_pool, key, current_quantity - quantity);
 
-            match self.bid_levels.get_mut(&level_key) {
-                Some(price_level) => {
-                    price_level.add_order(order);
-                }
-                None => {
-                    let mut new_level = PriceLevel::new(price);
-                    new_level.add_order(order);
-                    self.bid_levels.insert(level_key, new_level);
-                }
+                ExecutionReport::new(
+                    id,
+                    side,
+                    Vec::new(),
+                    Some(RestingOrder::new(id, price, quantity)),
+                    Quantity::ZERO,
+                )
             }
+            false => {
+                let (_, _, order) = self.remove_order(id).unwrap();
+                let filled_quantity = order.filled_quantity();
+                let history = (filled_quantity + quantity, filled_quantity);
 
-            self.orders.insert(id, (OrderSide::Bid, price));
-            Ok(id)
+                self.process_order(id, &request, Some(price), history)
+            }
         }
+        .with_owner(owner);
+
+        self.trigger_stops(&mut report);
+        self.publish_book_top(book_top);
+
+        Ok(report)
     }
 
-    pub fn add_limit_ask(&mut self, price: f32, mut quantity: f32) -> Result<u64, &str> {
-        if !Market::check_precision(price) {
-            return Err("Price must be positive and cannot have more then 2 decimal places");
+    pub fn apply(&mut self, command: Command) -> Result<CommandResult, MarketError> {
+        match command {
+            Command::NewOrder(request) => self.add_order(request).map(CommandResult::Executed),
+            Command::Cancel { order_id } => self
+                .cancel_limit_order(order_id)
+                .map(CommandResult::Cancelled),
+            Command::Replace {
+                order_id,
+                price,
+                quantity,
+            } => self
+                .modify_order(order_id, price, quantity)
+                .map(CommandResult::Executed),
+            Command::Halt => self.halt().map(|_| CommandResult::Halted),
+            Command::Resume => self.resume().map(|_| CommandResult::Resumed),
         }
+    }
 
-        // marketable order
-        if price <= self.highest_bid {
-            quantity = self.execute_ask(Some(price), quantity);
+    // Commands are journaled whether or not they succeed, a rejected command
+    // is rejected again on replay. A scheduled snapshot is taken first, while
+    // the market still reflects exactly the commands journaled so far, and
+    // only once those commands are synced so it never gets ahead of the journal.
+    fn record(&mut self, command: Command) -> Result<(), MarketError> {
+        let sequence = match self.journal.as_ref() {
+            Some(journal) => journal.sequence(),
+            None => return Ok(()),
+        };
+
+        if self
+            .snapshot_schedule
+            .as_ref()
+            .is_some_and(|schedule| schedule.is_due(sequence))
+            && self.journal.as_mut().unwrap().sync().is_ok()
+        {
+            let snapshot = self.snapshot();
+            // a failed snapshot only means recovery starts from an older one
+            let _ = self.snapshot_schedule.as_mut().unwrap().take(&snapshot);
         }
 
-        if quantity <= 0.0 {
-            return Ok(0);
-        } else {
-            let id = self.increment_total_orders();
+        let sequence = self
+            .journal
+            .as_mut()
+            .unwrap()
+            .append(&command)
+            .map_err(|_| MarketError::JournalFailed)?;
 
-            let order = Order::new(id, quantity);
+        if let Some(publisher) = &self.publisher {
+            publisher.publish(self.market_id, sequence, &command);
+        }
 
-            self.lowest_ask = f32::min(price, self.lowest_ask);
+        Ok(())
+    }
 
-            let level_key = PriceLevelKeyAsk::new(price);
+    fn execute_order(
+        &mut self,
+        id: u64,
+        request: &OrderRequest,
+        price: Option<Price>,
+    ) -> ExecutionReport {
+        let side = request.side();
+        let quantity = request.quantity();
+        let owner = request.owner();
+        let history = (quantity, Quantity::ZERO);
 
-            match self.ask_levels.get_mut(&level_key) {
-                Some(price_level) => {
-                    price_level.add_order(order);
-                }
-                None => {
-                    let mut new_level = PriceLevel::new(price);
-                    new_level.add_order(order);
-                    self.ask_levels.insert(level_key, new_level);
-                }
-            }
+        let report = match request.time_in_force() == TimeInForce::FillOrKill
+            && !self.can_fill(side, price, quantity, owner)
+        {
+            true => ExecutionReport::new(id, side, Vec::new(), None, quantity),
+            false => self.process_order(id, request, price, history),
+        }
+        .with_owner(owner);
+
+        // self-trade cancels were published during matching
+        let unfilled_quantity = report.cancelled_quantity() - report.self_traded_quantity();
 
-            self.orders.insert(id, (OrderSide::Ask, price));
-            Ok(id)
+        if !unfilled_quantity.is_zero() {
+            self.events.publish(MarketEvent::OrderCancelled {
+                order_id: id,
+                quantity: unfilled_quantity,
+                reason: CancelReason::Unfilled,
+            });
         }
+
+        report
     }
 
-    pub fn cancel_limit_order(&mut self, id: u64) -> bool {
-        match self.orders.get_mut(&id) {
-            Some((side, price)) => match side {
-                OrderSide::Ask => {
-                    let level = self.ask_levels.get_mut(&PriceLevelKeyAsk::new(*price));
-                    level.unwrap().cancel_order(id);
+    // Executes stop orders reached by the last traded price until none are
+    // left. Trades of a triggered order can trigger further stops, these are
+    // processed in the same loop so the outcome only depends on the book.
+    fn trigger_stops(&mut self, report: &mut ExecutionReport) {
+        while let Some(last_trade_price) = self.last_trade_price {
+            let stop = match self.stop_book.pop_triggered(last_trade_price) {
+                Some(stop) => stop,
+                None => break,
+            };
 
-                    if *price == self.lowest_ask {
-                        self.reset_best_ask(None);
-                    }
+            self.events.publish(MarketEvent::OrderTriggered {
+                order_id: stop.id(),
+            });
 
-                    self.orders.remove(&id);
+            let triggered = self.execute_order(stop.id(), stop.request(), stop.request().price());
+            report.add_triggered(triggered);
+        }
+    }
 
-                    true
-                }
-                OrderSide::Bid => {
-                    let level = self.bid_levels.get_mut(&PriceLevelKeyBid::new(*price));
-                    level.unwrap().cancel_order(id);
+    fn is_stop_triggered(&self, side: OrderSide, stop_price: Price) -> bool {
+        self.last_trade_price
+            .is_some_and(|last_trade_price| match side {
+                OrderSide::Bid => last_trade_price >= stop_price,
+                OrderSide::Ask => last_trade_price <= stop_price,
+            })
+    }
 
-                    if *price == self.highest_bid {
-                        self.reset_best_bid(None);
-                    }
+    // Matches an order against the book, the remainder of a good till cancel
+    // limit order is rested and any other remainder is cancelled. `history`
+    // holds the quantity the order was entered with and what it has already
+    // filled, which carry over when a modified order is re-entered.
+    fn process_order(
+        &mut self,
+        id: u64,
+        request: &OrderRequest,
+        price: Option<Price>,
+        history: (Quantity, Quantity),
+    ) -> ExecutionReport {
+        let side = request.side();
+        let (opposite, within_limit) = Self::matching_side(side);
+        let mut quantity = request.quantity();
+        let mut matching = Matching::new(id, request.owner(), self.config.self_trade_prevention);
 
-                    self.orders.remove(&id);
+        // marketable order
+        if self
+            .book
+            .best_price(opposite)
+            .is_some_and(|best| price.is_none_or(|price| within_limit(best, price)))
+        {
+            quantity = self.execute(side, price, quantity, &mut matching);
+        }
 
-                    true
-                }
-            },
-            None => false,
+        let report = match (price, request.time_in_force()) {
+            _ if quantity.is_zero() => {
+                ExecutionReport::new(id, side, matching.fills, None, Quantity::ZERO)
+            }
+            (Some(price), TimeInForce::GoodTillCancel)
+                if self.level_has_room(side, price, quantity) =>
+            {
+                let (original_quantity, filled_quantity) = history;
+                let filled_quantity =
+                    filled_quantity + matching.fills.iter().map(Fill::quantity).sum();
+                let order = Order::from_request(
+                    id,
+                    self.increment_total_sequence(),
+                    quantity,
+                    request,
+                    original_quantity,
+                    filled_quantity,
+                );
+
+                self.insert_order(side, price, order);
+
+                ExecutionReport::new(
+                    id,
+                    side,
+                    matching.fills,
+                    Some(RestingOrder::new(id, price, quantity)),
+                    Quantity::ZERO,
+                )
+            }
+            _ => ExecutionReport::new(id, side, matching.fills, None, quantity),
+        };
+
+        report.with_self_trades(matching.self_traded_quantity, matching.prevented)
+    }
+
+    // Side an order matches against, and whether a price on that side lies
+    // within the order's limit price
+    fn matching_side(side: OrderSide) -> (OrderSide, fn(Price, Price) -> bool) {
+        match side {
+            OrderSide::Bid => (OrderSide::Ask, |level, limit| level <= limit),
+            OrderSide::Ask => (OrderSide::Bid, |level, limit| level >= limit),
         }
     }
 
-    fn execute_ask(&mut self, price: Option<f32>, mut quantity: f32) -> f32 {
-        let price = price.unwrap_or_else(|| f32::NEG_INFINITY);
+    // Checks whether the opposite side holds enough quantity within the limit
+    // price to fill an order completely, without touching the book
+    fn can_fill(
+        &self,
+        side: OrderSide,
+        price: Option<Price>,
+        quantity: Quantity,
+        owner: Owner,
+    ) -> bool {
+        let (opposite, within_limit) = Self::matching_side(side);
 
-        let mut cursor = self
-            .bid_levels
-            .lower_bound_mut(Included(&PriceLevelKeyBid::new(self.highest_bid)));
+        let levels = self
+            .book
+            .levels(opposite)
+            .take_while(|level| price.is_none_or(|price| within_limit(level.price(), price)));
 
-        // iterate over price levels
-        while quantity > 0.0 && cursor.value().is_some() {
-            let level = cursor.value_mut().unwrap();
+        match self.config.self_trade_prevention {
+            Some(prevention) => {
+                self.has_quantity_without_self_trades(levels, quantity, owner, prevention)
+            }
+            None => Self::has_quantity(levels, quantity),
+        }
+    }
 
-            // iterate over orders within a single price level
-            while quantity > 0.0 && level.peek_next_order().is_some() {
-                let next_order = level.peek_next_order().unwrap();
-
-                match next_order.quantity() <= quantity {
-                    true => {
-                        quantity -= next_order.quantity();
-                        self.orders.remove(&next_order.id());
-                        level.remove_next_order();
-                    }
-                    false => {
-                        next_order.remove_quantity(quantity);
-                        quantity = 0.0;
-                    }
+    fn depth_levels<'b>(
+        levels: impl Iterator<Item = &'b PriceLevel> + 'b,
+    ) -> impl Iterator<Item = DepthLevel> + 'b {
+        levels.map(|level| DepthLevel::new(level.price(), level.quantity(), level.order_count()))
+    }
+
+    fn has_quantity<'b>(levels: impl Iterator<Item = &'b PriceLevel>, quantity: Quantity) -> bool {
+        let mut available = Quantity::ZERO;
+
+        for level in levels {
+            available = available.saturating_add(level.quantity() + level.hidden_quantity());
+
+            if available >= quantity {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // Walks the orders the way matching would. Orders of the same participant
+    // drop out of the book under cancel oldest, under any other mode reaching
+    // one cancels at least part of the incoming order.
+    fn has_quantity_without_self_trades<'b>(
+        &self,
+        levels: impl Iterator<Item = &'b PriceLevel>,
+        quantity: Quantity,
+        owner: Owner,
+        prevention: SelfTradePrevention,
+    ) -> bool {
+        let mut available = Quantity::ZERO;
+
+        for level in levels {
+            // only the displayed slice of an iceberg trades before the orders behind it
+            let mut displayed = Quantity::ZERO;
+            let mut total = Quantity::ZERO;
+
+            for order in level.orders(&self.order_pool) {
+                if !owner.same_participant(&order.owner()) {
+                    displayed += order.displayed_quantity();
+                    total += order.quantity();
+                } else if prevention != SelfTradePrevention::CancelOldest {
+                    return available.saturating_add(displayed) >= quantity;
                 }
             }
 
-            if quantity > 0.0 && level.price() >= price {
-                cursor.move_next();
-            } else {
-                break;
+            available = available.saturating_add(total);
+
+            if available >= quantity {
+                return true;
             }
         }
 
-        if quantity > 0.0 {
-            self.highest_bid = f32::NEG_INFINITY;
-            return quantity;
-        } else {
-            let cursor_price = cursor.value().unwrap().price();
-            self.reset_best_bid(Some(cursor_price));
-            return 0.0;
+        false
+    }
+
+    // Takes a resting order off the book and out of the order index, the
+    // price level is removed once its last order is gone
+    fn remove_order(&mut self, id: u64) -> Option<(OrderSide, Price, Order)> {
+        let (side, price, key) = self.orders.remove(&id)?;
+        let level = self.book.level_mut(side, price).unwrap();
+        let order = level.cancel_order(&mut self.order_pool, key);
+
+        if level.order_count() == 0 {
+            self.book.remove_level(side, price);
         }
+
+        Some((side, price, order.unwrap()))
     }
 
-    fn execute_bid(&mut self, price: Option<f32>, mut quantity: f32) -> f32 {
-        let price = price.unwrap_or_else(|| f32::INFINITY);
+    fn insert_order(&mut self, side: OrderSide, price: Price, order: Order) {
+        let id = order.id();
+        let key = self.order_pool.insert(order);
 
-        let mut cursor = self
-            .ask_levels
-            .lower_bound_mut(Included(&PriceLevelKeyAsk::new(self.lowest_ask)));
+        self.book
+            .level_entry(side, price)
+            .add_order(&mut self.order_pool, key);
+
+        self.orders.insert(id, (side, price, key));
+    }
+
+    // Matches incoming quantity against the opposite side of the book up to
+    // the limit price, returns the quantity left unmatched
+    fn execute(
+        &mut self,
+        side: OrderSide,
+        price: Option<Price>,
+        mut quantity: Quantity,
+        matching: &mut Matching,
+    ) -> Quantity {
+        let (opposite, within_limit) = Self::matching_side(side);
 
         // iterate over price levels
-        while quantity > 0.0 && cursor.value().is_some() {
-            let level = cursor.value_mut().unwrap();
+        self.book.match_levels(opposite, |level| {
+            if price.is_some_and(|price| !within_limit(level.price(), price)) {
+                return false;
+            }
 
             // iterate over orders within a single price level
-            while quantity > 0.0 && level.peek_next_order().is_some() {
-                let next_order = level.peek_next_order().unwrap();
-
-                match next_order.quantity() <= quantity {
-                    true => {
-                        quantity -= next_order.quantity();
-                        self.orders.remove(&next_order.id());
-                        level.remove_next_order();
+            while !quantity.is_zero() && level.next_order().is_some() {
+                let next_order = &self.order_pool[level.next_order().unwrap()];
+                let passive_id = next_order.id();
+                let passive_owner = next_order.owner();
+                let displayed_quantity = next_order.displayed_quantity();
+                let fill_quantity = displayed_quantity.min(quantity);
+                let passive_remaining = next_order.quantity() - fill_quantity;
+
+                if let Some(prevention) = matching.self_trade_prevention(&passive_owner) {
+                    quantity = Self::prevent_self_trade(
+                        prevention,
+                        quantity,
+                        level,
+                        &mut self.order_pool,
+                        &mut self.orders,
+                        &mut self.events,
+                        matching,
+                    );
+                    continue;
+                }
+
+                match (
+                    passive_remaining.is_zero(),
+                    fill_quantity == displayed_quantity,
+                ) {
+                    (true, _) => {
+                        self.orders.remove(&passive_id);
+                        level.remove_next_order(&mut self.order_pool);
                     }
-                    false => {
-                        next_order.remove_quantity(quantity);
-                        quantity = 0.0;
+                    // displayed slice of an iceberg used up, the next slice joins the back of the queue
+                    (false, true) => {
+                        level.fill_next_order(&mut self.order_pool, fill_quantity);
+                        self.total_sequence += 1;
+                        level.replenish_next_order(&mut self.order_pool, self.total_sequence);
                     }
+                    (false, false) => level.fill_next_order(&mut self.order_pool, fill_quantity),
                 }
+
+                quantity -= fill_quantity;
+                self.total_trades += 1;
+                self.last_trade_price = Some(level.price());
+
+                let fill = Fill::new(
+                    self.total_trades,
+                    matching.id,
+                    passive_id,
+                    level.price(),
+                    fill_quantity,
+                    side,
+                )
+                .with_owners(matching.owner, passive_owner);
+
+                self.events
+                    .publish(Self::fill_event(passive_id, fill, passive_remaining));
+                self.events
+                    .publish(Self::fill_event(matching.id, fill, quantity));
+                matching.fills.push(fill);
             }
 
-            if quantity > 0.0 && level.price() <= price {
-                cursor.move_next();
-            } else {
-                break;
+            // move on to the next level while quantity is left, the book drops the exhausted one
+            !quantity.is_zero()
+        });
+
+        quantity
+    }
+
+    // Cancels quantity of the incoming order and of the next order of a level
+    // instead of trading them against each other, returns the incoming
+    // quantity left to match
+    fn prevent_self_trade(
+        prevention: SelfTradePrevention,
+        quantity: Quantity,
+        level: &mut PriceLevel,
+        order_pool: &mut Slab<Order>,
+        orders: &mut HashMap<u64, (OrderSide, Price, usize)>,
+        events: &mut EventPublisher,
+        matching: &mut Matching,
+    ) -> Quantity {
+        let key = level.next_order().unwrap();
+        let passive_id = order_pool[key].id();
+        let passive_quantity = order_pool[key].quantity();
+
+        let (cancelled, passive_cancelled) =
+            prevention.cancel_quantities(quantity, passive_quantity);
+
+        if !passive_cancelled.is_zero() {
+            match passive_cancelled == passive_quantity {
+                true => {
+                    orders.remove(&passive_id);
+                    level.remove_next_order(order_pool);
+                }
+                false => level.reduce_order(order_pool, key, passive_cancelled),
             }
+
+            events.publish(MarketEvent::OrderCancelled {
+                order_id: passive_id,
+                quantity: passive_cancelled,
+                reason: CancelReason::SelfTrade,
+            });
+
+            matching.prevented.push(RestingOrder::new(
+                passive_id,
+                level.price(),
+                passive_cancelled,
+            ));
         }
 
-        if quantity > 0.0 {
-            self.lowest_ask = f32::INFINITY;
-            return quantity;
-        } else {
-            let cursor_price = cursor.value().unwrap().price();
-            self.reset_best_ask(Some(cursor_price));
-            return 0.0;
+        if !cancelled.is_zero() {
+            events.publish(MarketEvent::OrderCancelled {
+                order_id: matching.id,
+                quantity: cancelled,
+                reason: CancelReason::SelfTrade,
+            });
+
+            matching.self_traded_quantity += cancelled;
         }
+
+        quantity - cancelled
     }
 
-    fn reset_best_bid(&mut self, mut cursor: Option<f32>) {
-        if cursor.is_none() {
-            cursor = Some(self.highest_bid);
+    // Validates an order and returns the price it should be entered at, which
+    // differs from the requested price only for sliding post-only orders.
+    // Post-only is not checked for stop orders as they never rest on entry.
+    // A modified order replaces the quantity it rests with at its current price.
+    fn validate_order(
+        &mut self,
+        request: &OrderRequest,
+        replaced: Option<(Price, Quantity)>,
+    ) -> Result<Option<Price>, MarketError> {
+        let side = request.side();
+        let price = request.price();
+        let quantity = request.quantity();
+
+        // quantity the order would add to the level it rests at
+        let added = |price: Price| match replaced {
+            Some((current_price, current_quantity)) if current_price == price => {
+                quantity - quantity.min(current_quantity)
+            }
+            _ => quantity,
+        };
+
+        let result = self
+            .check_order(request)
+            .and_then(|_| match (request.post_only(), request.stop_price()) {
+                (Some(post_only), None) => self.check_post_only(side, price, post_only),
+                _ => Ok(price),
+            })
+            .and_then(|price| match (price, request.stop_price()) {
+                // the level of a stop order is checked when its remainder would rest
+                (Some(price), None) if !self.level_has_room(side, price, added(price)) => {
+                    Err(MarketError::LevelFull)
+                }
+                _ => Ok(price),
+            });
+
+        if let Err(reason) = result {
+            self.events
+                .publish(MarketEvent::OrderRejected { side, reason });
         }
 
-        let mut cursor = self
-            .bid_levels
-            .lower_bound(Included(&&PriceLevelKeyBid::new(cursor.unwrap())));
+        result
+    }
 
-        let mut final_has_quantity = false;
+    fn check_order(&self, request: &OrderRequest) -> Result<(), MarketError> {
+        if self.halted {
+            return Err(MarketError::MarketHalted);
+        }
 
-        while cursor.value().is_some() {
-            let level = cursor.value().unwrap();
+        for price in [request.price(), request.stop_price()]
+            .into_iter()
+            .flatten()
+        {
+            self.check_price(price)?;
+        }
 
-            if level.quantity() > 0.0 {
-                final_has_quantity = true;
-                self.highest_bid = level.price();
-                break;
-            } else {
-                cursor.move_next();
-            }
+        if request
+            .price()
+            .is_some_and(|price| !self.book.contains_price(price))
+        {
+            return Err(MarketError::PriceOutOfRange);
         }
 
-        if !final_has_quantity {
-            self.highest_bid = f32::NEG_INFINITY;
+        let quantity = request.quantity();
+
+        self.check_quantity(quantity)?;
+
+        match request.display_quantity() {
+            Some(display_quantity) => self.check_quantity(display_quantity),
+            None => Ok(()),
         }
     }
 
-    fn reset_best_ask(&mut self, mut cursor: Option<f32>) {
-        if cursor.is_none() {
-            cursor = Some(self.lowest_ask);
+    fn check_quantity(&self, quantity: Quantity) -> Result<(), MarketError> {
+        if quantity.is_zero() {
+            return Err(MarketError::ZeroQuantity);
+        }
+
+        if !quantity.lots().is_multiple_of(self.config.lot_size.lots()) {
+            return Err(MarketError::InvalidQuantity);
         }
 
-        let mut cursor = self
-            .ask_levels
-            .lower_bound(Included(&&PriceLevelKeyAsk::new(cursor.unwrap())));
+        Ok(())
+    }
 
-        let mut final_has_quantity = false;
+    // Whether an order can rest at a price without the quantity of its level,
+    // hidden reserve included, overflowing
+    fn level_has_room(&self, side: OrderSide, price: Price, quantity: Quantity) -> bool {
+        self.book.level(side, price).is_none_or(|level| {
+            level
+                .quantity()
+                .checked_add(level.hidden_quantity())
+                .and_then(|total| total.checked_add(quantity))
+                .is_some()
+        })
+    }
 
-        while cursor.value().is_some() {
-            let level = cursor.value().unwrap();
+    fn check_price(&self, price: Price) -> Result<(), MarketError> {
+        if !price.is_positive() {
+            return Err(MarketError::InvalidPrice);
+        }
 
-            if level.quantity() > 0.0 {
-                final_has_quantity = true;
-                self.lowest_ask = level.price();
-                break;
-            } else {
-                cursor.move_next();
+        if price.ticks() % self.config.tick_size.ticks() != 0 {
+            return Err(MarketError::InvalidTick);
+        }
+
+        Ok(())
+    }
+
+    fn check_post_only(
+        &self,
+        side: OrderSide,
+        price: Option<Price>,
+        post_only: PostOnly,
+    ) -> Result<Option<Price>, MarketError> {
+        let opposite = match side {
+            OrderSide::Bid => self
+                .best_ask()
+                .filter(|lowest_ask| price.is_none_or(|price| price >= *lowest_ask)),
+            OrderSide::Ask => self
+                .best_bid()
+                .filter(|highest_bid| price.is_none_or(|price| price <= *highest_bid)),
+        };
+
+        let opposite = match opposite {
+            Some(opposite) => opposite,
+            None => return Ok(price),
+        };
+
+        // reprice one tick away from the opposite best price, an order that
+        // would slide past the largest price is rejected
+        let slide_price = match side {
+            OrderSide::Bid => opposite.checked_sub(self.config.tick_size),
+            OrderSide::Ask => opposite.checked_add(self.config.tick_size),
+        };
+
+        match (post_only, price, slide_price) {
+            (PostOnly::Slide, Some(_), Some(slide_price))
+                if slide_price.is_positive() && self.book.contains_price(slide_price) =>
+            {
+                Ok(Some(slide_price))
             }
+            _ => Err(MarketError::WouldTakeLiquidity),
         }
+    }
+
+    fn book_top(&self) -> (Option<Price>, Option<Price>) {
+        (self.best_bid(), self.best_ask())
+    }
 
-        if !final_has_quantity {
-            self.lowest_ask = f32::INFINITY;
+    fn publish_book_top(&mut self, previous: (Option<Price>, Option<Price>)) {
+        if self.book_top() != previous {
+            self.events.publish(MarketEvent::BookTopChanged {
+                best_bid: self.best_bid(),
+                best_ask: self.best_ask(),
+            });
         }
     }
 
-    fn increment_total_orders(&mut self) -> u64 {
+    fn fill_event(order_id: u64, fill: Fill, remaining_quantity: Quantity) -> MarketEvent {
+        match remaining_quantity.is_zero() {
+            true => MarketEvent::OrderFilled { order_id, fill },
+            false => MarketEvent::OrderPartiallyFilled {
+                order_id,
+                fill,
+                remaining_quantity,
+            },
+        }
+    }
+
+    // Fails rather than let the counter carry into the market id bits
+    fn increment_total_orders(&mut self) -> Result<u64, MarketError> {
+        if self.total_orders >= MAX_ORDER_COUNTER {
+            return Err(MarketError::OrderIdsExhausted);
+        }
+
         self.total_orders += 1;
-        self.total_orders
+        Ok((self.market_id as u64) << MARKET_ID_SHIFT | self.total_orders)
     }
 
-    fn check_precision(float: f32) -> bool {
-        (float * 100.0).fract() == 0.0 && float > 0.0
+    fn increment_total_sequence(&mut self) -> u64 {
+        self.total_sequence += 1;
+        self.total_sequence
     }
 }
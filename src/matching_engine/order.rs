@@ -1,25 +1,351 @@
-#[derive(Debug)]
+use super::price::Price;
+use super::quantity::Quantity;
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OrderType {
     Limit,
+    Market,
+    // Becomes a market order once the last traded price reaches the stop price
+    StopMarket,
+    // Becomes a limit order once the last traded price reaches the stop price
+    StopLimit,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OrderSide {
     Bid,
     Ask,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeInForce {
+    // Rest any unfilled quantity on the book
+    GoodTillCancel,
+    // Fill what is possible and cancel the rest
+    ImmediateOrCancel,
+    // Fill the entire quantity immediately or do nothing
+    FillOrKill,
+}
+
+// Behaviour of a post-only order that would take liquidity on entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostOnly {
+    Reject,
+    // Reprice one tick away from the opposite best price
+    Slide,
+}
+
+// How a market keeps an incoming order from trading against a resting order
+// of the same account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfTradePrevention {
+    // Cancel the rest of the incoming order
+    CancelNewest,
+    // Cancel the resting order and carry on matching
+    CancelOldest,
+    CancelBoth,
+    // Take the smaller quantity off both orders, cancelling whichever is used up
+    DecrementAndCancel,
+}
+
+impl SelfTradePrevention {
+    // Quantities to cancel from the incoming and from the resting order
+    pub fn cancel_quantities(&self, incoming: Quantity, resting: Quantity) -> (Quantity, Quantity) {
+        match self {
+            SelfTradePrevention::CancelNewest => (incoming, Quantity::ZERO),
+            SelfTradePrevention::CancelOldest => (Quantity::ZERO, resting),
+            SelfTradePrevention::CancelBoth => (incoming, resting),
+            SelfTradePrevention::DecrementAndCancel => {
+                let quantity = incoming.min(resting);
+                (quantity, quantity)
+            }
+        }
+    }
+}
+
+// Participant an order is entered for, account 0 stands for an order
+// entered without one. The trader or session that entered the order is
+// optional and does not affect matching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Owner {
+    account: u64,
+    trader: Option<u64>,
+}
+
+impl Owner {
+    pub fn new(account: u64) -> Self {
+        Owner {
+            account,
+            trader: None,
+        }
+    }
+
+    pub fn with_trader(mut self, trader: u64) -> Self {
+        self.trader = Some(trader);
+        self
+    }
+
+    pub fn account(&self) -> u64 {
+        self.account
+    }
+
+    pub fn trader(&self) -> Option<u64> {
+        self.trader
+    }
+
+    // Orders without an account never count as trading with themselves
+    pub fn same_participant(&self, other: &Owner) -> bool {
+        self.account != 0 && self.account == other.account
+    }
+}
+
+// A new order as submitted to a market
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderRequest {
+    side: OrderSide,
+    order_type: OrderType,
+    price: Option<Price>,
+    stop_price: Option<Price>,
+    quantity: Quantity,
+    // Peak size shown to the market for iceberg orders
+    display_quantity: Option<Quantity>,
+    time_in_force: TimeInForce,
+    post_only: Option<PostOnly>,
+    owner: Owner,
+}
+
+impl OrderRequest {
+    pub fn limit(side: OrderSide, price: Price, quantity: Quantity) -> Self {
+        OrderRequest {
+            side,
+            order_type: OrderType::Limit,
+            price: Some(price),
+            stop_price: None,
+            quantity,
+            display_quantity: None,
+            time_in_force: TimeInForce::GoodTillCancel,
+            post_only: None,
+            owner: Owner::default(),
+        }
+    }
+
+    // Market orders never rest, any unfilled quantity is cancelled
+    pub fn market(side: OrderSide, quantity: Quantity) -> Self {
+        OrderRequest {
+            side,
+            order_type: OrderType::Market,
+            price: None,
+            stop_price: None,
+            quantity,
+            display_quantity: None,
+            time_in_force: TimeInForce::ImmediateOrCancel,
+            post_only: None,
+            owner: Owner::default(),
+        }
+    }
+
+    // Buy stops trigger when the last traded price rises to the stop price,
+    // sell stops when it falls to the stop price
+    pub fn stop_market(side: OrderSide, stop_price: Price, quantity: Quantity) -> Self {
+        OrderRequest {
+            order_type: OrderType::StopMarket,
+            stop_price: Some(stop_price),
+            ..OrderRequest::market(side, quantity)
+        }
+    }
+
+    pub fn stop_limit(
+        side: OrderSide,
+        stop_price: Price,
+        price: Price,
+        quantity: Quantity,
+    ) -> Self {
+        OrderRequest {
+            order_type: OrderType::StopLimit,
+            stop_price: Some(stop_price),
+            ..OrderRequest::limit(side, price, quantity)
+        }
+    }
+
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    pub fn with_post_only(mut self, post_only: PostOnly) -> Self {
+        self.post_only = Some(post_only);
+        self
+    }
+
+    // Turns the order into an iceberg that only shows `display_quantity` at a
+    // time and keeps the rest in reserve
+    pub fn with_display_quantity(mut self, display_quantity: Quantity) -> Self {
+        self.display_quantity = Some(display_quantity);
+        self
+    }
+
+    pub fn with_owner(mut self, owner: Owner) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    pub fn side(&self) -> OrderSide {
+        self.side
+    }
+
+    pub fn order_type(&self) -> OrderType {
+        self.order_type
+    }
+
+    // Limit price, None for market orders
+    pub fn price(&self) -> Option<Price> {
+        self.price
+    }
+
+    // Trigger price, None unless this is a stop order
+    pub fn stop_price(&self) -> Option<Price> {
+        self.stop_price
+    }
+
+    pub fn quantity(&self) -> Quantity {
+        self.quantity
+    }
+
+    pub fn display_quantity(&self) -> Option<Quantity> {
+        self.display_quantity
+    }
+
+    pub fn time_in_force(&self) -> TimeInForce {
+        self.time_in_force
+    }
+
+    pub fn post_only(&self) -> Option<PostOnly> {
+        self.post_only
+    }
+
+    pub fn owner(&self) -> Owner {
+        self.owner
+    }
+}
+
 #[derive(Debug)]
 pub struct Order {
     id: u64,
-    quantity: f32,
+    // Position in the price level queue, lower sequences are matched first
+    sequence: u64,
+    // Remaining quantity including any hidden reserve
+    quantity: Quantity,
+    original_quantity: Quantity,
+    filled_quantity: Quantity,
+    displayed_quantity: Quantity,
+    // Peak size of an iceberg order, None if the whole quantity is displayed
+    display_quantity: Option<Quantity>,
+    entry_time: SystemTime,
+    owner: Owner,
+    // Kept so a modified order is re-entered with the same instructions
+    time_in_force: TimeInForce,
+    post_only: Option<PostOnly>,
+    // Slab keys of the neighbouring orders in the price level queue
+    previous: Option<usize>,
+    next: Option<usize>,
 }
 
 impl Order {
-    pub fn new(id: u64, quantity: f32) -> Self {
+    pub fn new(id: u64, sequence: u64, quantity: Quantity) -> Self {
+        Order {
+            id,
+            sequence,
+            quantity,
+            original_quantity: quantity,
+            filled_quantity: Quantity::ZERO,
+            displayed_quantity: quantity,
+            display_quantity: None,
+            entry_time: SystemTime::now(),
+            owner: Owner::default(),
+            time_in_force: TimeInForce::GoodTillCancel,
+            post_only: None,
+            previous: None,
+            next: None,
+        }
+    }
+
+    // Resting part of a request, `original_quantity` and `filled_quantity`
+    // cover everything the order has been entered with and traded so far
+    pub fn from_request(
+        id: u64,
+        sequence: u64,
+        quantity: Quantity,
+        request: &OrderRequest,
+        original_quantity: Quantity,
+        filled_quantity: Quantity,
+    ) -> Self {
+        let order = match request.display_quantity() {
+            Some(display_quantity) => Order::iceberg(id, sequence, quantity, display_quantity),
+            None => Order::new(id, sequence, quantity),
+        };
+
+        Order {
+            original_quantity,
+            filled_quantity,
+            owner: request.owner(),
+            time_in_force: request.time_in_force(),
+            post_only: request.post_only(),
+            ..order
+        }
+    }
+
+    // Rebuilds an order from a snapshot of it, the order is not linked into a level
+    pub fn from_view(view: &OrderView) -> Self {
+        Order::new(view.id(), view.sequence(), view.original_quantity())
+            .with_state(
+                view.remaining_quantity(),
+                view.filled_quantity(),
+                view.displayed_quantity(),
+                view.display_quantity(),
+                view.entry_time(),
+            )
+            .with_owner(view.owner())
+            .with_instructions(view.time_in_force(), view.post_only())
+    }
+
+    // Restores the progress of an order that was entered earlier
+    pub fn with_state(
+        mut self,
+        quantity: Quantity,
+        filled_quantity: Quantity,
+        displayed_quantity: Quantity,
+        display_quantity: Option<Quantity>,
+        entry_time: SystemTime,
+    ) -> Self {
+        self.quantity = quantity;
+        self.filled_quantity = filled_quantity;
+        self.displayed_quantity = displayed_quantity;
+        self.display_quantity = display_quantity;
+        self.entry_time = entry_time;
+        self
+    }
+
+    pub fn with_owner(mut self, owner: Owner) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    pub fn with_instructions(
+        mut self,
+        time_in_force: TimeInForce,
+        post_only: Option<PostOnly>,
+    ) -> Self {
+        self.time_in_force = time_in_force;
+        self.post_only = post_only;
+        self
+    }
+
+    pub fn iceberg(id: u64, sequence: u64, quantity: Quantity, display_quantity: Quantity) -> Self {
         Order {
-            id: id,
-            quantity: quantity,
+            displayed_quantity: quantity.min(display_quantity),
+            display_quantity: Some(display_quantity),
+            ..Order::new(id, sequence, quantity)
         }
     }
 
@@ -27,11 +353,177 @@ impl Order {
         self.id
     }
 
-    pub fn remove_quantity(&mut self, quantity: f32) {
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    // Removes filled quantity from the displayed part of the order
+    pub fn remove_quantity(&mut self, quantity: Quantity) {
         self.quantity -= quantity;
+        self.filled_quantity += quantity;
+        self.displayed_quantity -= quantity;
+    }
+
+    // Removes quantity from the hidden reserve first, then from the displayed part
+    pub fn reduce_quantity(&mut self, quantity: Quantity) {
+        self.quantity -= quantity;
+        self.displayed_quantity = self.displayed_quantity.min(self.quantity);
+    }
+
+    // Displays the next slice of an iceberg order under a new queue position
+    pub fn replenish(&mut self, sequence: u64) {
+        self.sequence = sequence;
+        self.displayed_quantity = match self.display_quantity {
+            Some(display_quantity) => self.quantity.min(display_quantity),
+            None => self.quantity,
+        };
     }
 
-    pub fn quantity(&self) -> f32 {
+    pub fn quantity(&self) -> Quantity {
         self.quantity
     }
+
+    // Quantity the order was entered with
+    pub fn original_quantity(&self) -> Quantity {
+        self.original_quantity
+    }
+
+    pub fn filled_quantity(&self) -> Quantity {
+        self.filled_quantity
+    }
+
+    pub fn displayed_quantity(&self) -> Quantity {
+        self.displayed_quantity
+    }
+
+    pub fn hidden_quantity(&self) -> Quantity {
+        self.quantity - self.displayed_quantity
+    }
+
+    pub fn display_quantity(&self) -> Option<Quantity> {
+        self.display_quantity
+    }
+
+    pub fn entry_time(&self) -> SystemTime {
+        self.entry_time
+    }
+
+    pub fn owner(&self) -> Owner {
+        self.owner
+    }
+
+    pub fn time_in_force(&self) -> TimeInForce {
+        self.time_in_force
+    }
+
+    pub fn post_only(&self) -> Option<PostOnly> {
+        self.post_only
+    }
+
+    pub fn previous(&self) -> Option<usize> {
+        self.previous
+    }
+
+    pub fn next(&self) -> Option<usize> {
+        self.next
+    }
+
+    pub fn set_previous(&mut self, previous: Option<usize>) {
+        self.previous = previous;
+    }
+
+    pub fn set_next(&mut self, next: Option<usize>) {
+        self.next = next;
+    }
+}
+
+// Snapshot of a resting order together with its position in the book
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderView {
+    id: u64,
+    side: OrderSide,
+    price: Price,
+    sequence: u64,
+    original_quantity: Quantity,
+    remaining_quantity: Quantity,
+    filled_quantity: Quantity,
+    displayed_quantity: Quantity,
+    display_quantity: Option<Quantity>,
+    entry_time: SystemTime,
+    owner: Owner,
+    time_in_force: TimeInForce,
+    post_only: Option<PostOnly>,
+}
+
+impl OrderView {
+    pub fn new(side: OrderSide, price: Price, order: &Order) -> Self {
+        OrderView {
+            id: order.id(),
+            side,
+            price,
+            sequence: order.sequence(),
+            original_quantity: order.original_quantity(),
+            remaining_quantity: order.quantity(),
+            filled_quantity: order.filled_quantity(),
+            displayed_quantity: order.displayed_quantity(),
+            display_quantity: order.display_quantity(),
+            entry_time: order.entry_time(),
+            owner: order.owner(),
+            time_in_force: order.time_in_force(),
+            post_only: order.post_only(),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn side(&self) -> OrderSide {
+        self.side
+    }
+
+    pub fn price(&self) -> Price {
+        self.price
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn original_quantity(&self) -> Quantity {
+        self.original_quantity
+    }
+
+    pub fn remaining_quantity(&self) -> Quantity {
+        self.remaining_quantity
+    }
+
+    pub fn filled_quantity(&self) -> Quantity {
+        self.filled_quantity
+    }
+
+    pub fn displayed_quantity(&self) -> Quantity {
+        self.displayed_quantity
+    }
+
+    // Peak size of an iceberg order
+    pub fn display_quantity(&self) -> Option<Quantity> {
+        self.display_quantity
+    }
+
+    pub fn entry_time(&self) -> SystemTime {
+        self.entry_time
+    }
+
+    pub fn owner(&self) -> Owner {
+        self.owner
+    }
+
+    pub fn time_in_force(&self) -> TimeInForce {
+        self.time_in_force
+    }
+
+    pub fn post_only(&self) -> Option<PostOnly> {
+        self.post_only
+    }
 }
@@ -0,0 +1,188 @@
+use super::order::OrderSide;
+use super::price::Price;
+use super::price_level::PriceLevel;
+use super::price_level_key::*;
+use std::collections::BTreeMap;
+#[cfg(feature = "btree_cursors")]
+use std::ops::Bound::Unbounded;
+
+// Storage for the price levels of both sides of a market. Only levels holding
+// at least one order are part of the book.
+pub trait OrderBook {
+    // Highest bid or lowest ask
+    fn best_price(&self, side: OrderSide) -> Option<Price>;
+
+    fn level(&self, side: OrderSide, price: Price) -> Option<&PriceLevel>;
+
+    fn level_mut(&mut self, side: OrderSide, price: Price) -> Option<&mut PriceLevel>;
+
+    // Level at a price, an empty one is added if the book has none
+    fn level_entry(&mut self, side: OrderSide, price: Price) -> &mut PriceLevel;
+
+    // Drops a level once its last order is gone
+    fn remove_level(&mut self, side: OrderSide, price: Price);
+
+    // Levels of one side, best price first
+    fn levels(&self, side: OrderSide) -> impl Iterator<Item = &PriceLevel> + '_;
+
+    fn level_count(&self, side: OrderSide) -> usize;
+
+    // Whether the book is able to hold a level at this price
+    fn contains_price(&self, _price: Price) -> bool {
+        true
+    }
+
+    // Visits levels best price first for as long as `visit` returns true,
+    // levels left without orders are removed from the book
+    fn match_levels(&mut self, side: OrderSide, mut visit: impl FnMut(&mut PriceLevel) -> bool) {
+        while let Some(price) = self.best_price(side) {
+            let level = self.level_mut(side, price).unwrap();
+            let next = visit(level);
+            let exhausted = level.order_count() == 0;
+
+            if exhausted {
+                self.remove_level(side, price);
+            }
+
+            if !next || !exhausted {
+                break;
+            }
+        }
+    }
+}
+
+// Levels kept in price ordered trees, suited to any price range
+#[derive(Debug)]
+pub struct TreeBook {
+    ask_levels: BTreeMap<PriceLevelKeyAsk, PriceLevel>,
+    bid_levels: BTreeMap<PriceLevelKeyBid, PriceLevel>,
+}
+
+impl TreeBook {
+    pub fn new() -> Self {
+        TreeBook {
+            ask_levels: BTreeMap::new(),
+            bid_levels: BTreeMap::new(),
+        }
+    }
+
+    #[cfg(not(feature = "btree_cursors"))]
+    fn match_tree<K: Ord>(
+        levels: &mut BTreeMap<K, PriceLevel>,
+        mut visit: impl FnMut(&mut PriceLevel) -> bool,
+    ) {
+        while let Some(mut entry) = levels.first_entry() {
+            let next = visit(entry.get_mut());
+            let exhausted = entry.get().order_count() == 0;
+
+            // an exhausted level is dropped from the book through its entry, without a second lookup
+            if exhausted {
+                entry.remove();
+            }
+
+            if !next || !exhausted {
+                break;
+            }
+        }
+    }
+
+    // Nightly only, walks the tree with a cursor instead of starting each
+    // level from the root
+    #[cfg(feature = "btree_cursors")]
+    fn match_tree<K: Ord>(
+        levels: &mut BTreeMap<K, PriceLevel>,
+        mut visit: impl FnMut(&mut PriceLevel) -> bool,
+    ) {
+        let mut cursor = levels.lower_bound_mut(Unbounded);
+
+        while let Some((_, level)) = cursor.peek_next() {
+            let next = visit(level);
+            let exhausted = level.order_count() == 0;
+
+            // an exhausted level is dropped from the book, the cursor moves on to the next one
+            if exhausted {
+                cursor.remove_next();
+            }
+
+            if !next || !exhausted {
+                break;
+            }
+        }
+    }
+}
+
+impl Default for TreeBook {
+    fn default() -> Self {
+        TreeBook::new()
+    }
+}
+
+impl OrderBook for TreeBook {
+    fn best_price(&self, side: OrderSide) -> Option<Price> {
+        match side {
+            OrderSide::Bid => self
+                .bid_levels
+                .first_key_value()
+                .map(|(key, _)| key.get_price()),
+            OrderSide::Ask => self
+                .ask_levels
+                .first_key_value()
+                .map(|(key, _)| key.get_price()),
+        }
+    }
+
+    fn level(&self, side: OrderSide, price: Price) -> Option<&PriceLevel> {
+        match side {
+            OrderSide::Bid => self.bid_levels.get(&PriceLevelKeyBid::new(price)),
+            OrderSide::Ask => self.ask_levels.get(&PriceLevelKeyAsk::new(price)),
+        }
+    }
+
+    fn level_mut(&mut self, side: OrderSide, price: Price) -> Option<&mut PriceLevel> {
+        match side {
+            OrderSide::Bid => self.bid_levels.get_mut(&PriceLevelKeyBid::new(price)),
+            OrderSide::Ask => self.ask_levels.get_mut(&PriceLevelKeyAsk::new(price)),
+        }
+    }
+
+    fn level_entry(&mut self, side: OrderSide, price: Price) -> &mut PriceLevel {
+        match side {
+            OrderSide::Bid => self
+                .bid_levels
+                .entry(PriceLevelKeyBid::new(price))
+                .or_insert_with(|| PriceLevel::new(price)),
+            OrderSide::Ask => self
+                .ask_levels
+                .entry(PriceLevelKeyAsk::new(price))
+                .or_insert_with(|| PriceLevel::new(price)),
+        }
+    }
+
+    fn remove_level(&mut self, side: OrderSide, price: Price) {
+        match side {
+            OrderSide::Bid => self.bid_levels.remove(&PriceLevelKeyBid::new(price)),
+            OrderSide::Ask => self.ask_levels.remove(&PriceLevelKeyAsk::new(price)),
+        };
+    }
+
+    fn levels(&self, side: OrderSide) -> impl Iterator<Item = &PriceLevel> + '_ {
+        let bids = (side == OrderSide::Bid).then(|| self.bid_levels.values());
+        let asks = (side == OrderSide::Ask).then(|| self.ask_levels.values());
+
+        bids.into_iter().flatten().chain(asks.into_iter().flatten())
+    }
+
+    fn level_count(&self, side: OrderSide) -> usize {
+        match side {
+            OrderSide::Bid => self.bid_levels.len(),
+            OrderSide::Ask => self.ask_levels.len(),
+        }
+    }
+
+    fn match_levels(&mut self, side: OrderSide, visit: impl FnMut(&mut PriceLevel) -> bool) {
+        match side {
+            OrderSide::Bid => TreeBook::match_tree(&mut self.bid_levels, visit),
+            OrderSide::Ask => TreeBook::match_tree(&mut self.ask_levels, visit),
+        }
+    }
+}
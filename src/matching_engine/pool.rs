@@ -0,0 +1,105 @@
+use std::ops::{Index, IndexMut};
+
+#[derive(Debug)]
+enum Entry<T> {
+    Occupied(T),
+    // Link to the next free slot
+    Vacant(Option<usize>),
+}
+
+// Stores values in a single growable array and hands out their index as a
+// key. Removed slots are reused by later inserts, so once the slab has grown
+// to the working set size inserts no longer allocate.
+#[derive(Debug)]
+pub struct Slab<T> {
+    entries: Vec<Entry<T>>,
+    next_free: Option<usize>,
+    len: usize,
+}
+
+impl<T> Slab<T> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Slab {
+            entries: Vec::with_capacity(capacity),
+            next_free: None,
+            len: 0,
+        }
+    }
+
+    pub fn insert(&mut self, value: T) -> usize {
+        self.len += 1;
+
+        match self.next_free {
+            Some(key) => {
+                if let Entry::Vacant(next_free) = self.entries[key] {
+                    self.next_free = next_free;
+                }
+
+                self.entries[key] = Entry::Occupied(value);
+                key
+            }
+            None => {
+                self.entries.push(Entry::Occupied(value));
+                self.entries.len() - 1
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: usize) -> Option<T> {
+        let entry = self.entries.get_mut(key)?;
+
+        if let Entry::Vacant(_) = entry {
+            return None;
+        }
+
+        let removed = std::mem::replace(entry, Entry::Vacant(self.next_free));
+        self.next_free = Some(key);
+        self.len -= 1;
+
+        match removed {
+            Entry::Occupied(value) => Some(value),
+            Entry::Vacant(_) => unreachable!(),
+        }
+    }
+
+    pub fn get(&self, key: usize) -> Option<&T> {
+        match self.entries.get(key) {
+            Some(Entry::Occupied(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+        match self.entries.get_mut(key) {
+            Some(Entry::Occupied(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.capacity()
+    }
+}
+
+// Panics if the key is vacant
+impl<T> Index<usize> for Slab<T> {
+    type Output = T;
+
+    fn index(&self, key: usize) -> &T {
+        self.get(key).expect("vacant slab entry")
+    }
+}
+
+impl<T> IndexMut<usize> for Slab<T> {
+    fn index_mut(&mut self, key: usize) -> &mut T {
+        self.get_mut(key).expect("vacant slab entry")
+    }
+}
@@ -0,0 +1,64 @@
+use std::ops::{Add, Sub};
+
+// Fixed-point price expressed as an integer number of ticks. Markets, the
+// protocol and journals only deal in ticks, the number of decimal places a
+// tick stands for is up to the caller converting with from_f64 and to_f64.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Price(i64);
+
+impl Price {
+    pub const fn from_ticks(ticks: i64) -> Self {
+        Price(ticks)
+    }
+
+    // Returns None if the value cannot be represented exactly with `scale` decimal places
+    pub fn from_f64(value: f64, scale: u32) -> Option<Self> {
+        let scaled = value * 10f64.powi(scale as i32);
+        let ticks = scaled.round();
+
+        if !ticks.is_finite()
+            || (scaled - ticks).abs() > 1e-9 * scaled.abs().max(1.0)
+            || ticks.abs() > i64::MAX as f64
+        {
+            return None;
+        }
+
+        Some(Price(ticks as i64))
+    }
+
+    pub fn ticks(&self) -> i64 {
+        self.0
+    }
+
+    pub fn to_f64(&self, scale: u32) -> f64 {
+        self.0 as f64 / 10f64.powi(scale as i32)
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.0 > 0
+    }
+
+    pub fn checked_add(self, other: Price) -> Option<Price> {
+        self.0.checked_add(other.0).map(Price)
+    }
+
+    pub fn checked_sub(self, other: Price) -> Option<Price> {
+        self.0.checked_sub(other.0).map(Price)
+    }
+}
+
+impl Add for Price {
+    type Output = Price;
+
+    fn add(self, other: Price) -> Price {
+        Price(self.0 + other.0)
+    }
+}
+
+impl Sub for Price {
+    type Output = Price;
+
+    fn sub(self, other: Price) -> Price {
+        Price(self.0 - other.0)
+    }
+}
@@ -1,71 +1,144 @@
 use super::order::Order;
-use std::collections::BTreeMap;
+use super::pool::Slab;
+use super::price::Price;
+use super::quantity::Quantity;
+use std::iter;
 
+// Queue of orders at a single price. The orders themselves live in the
+// market's order slab and are linked to their neighbours by slab key, so
+// appending, popping the front and cancelling by key are all O(1).
 #[derive(Debug)]
 pub struct PriceLevel {
-    price: f32,
-    quantity: f32,
-    orders: BTreeMap<u64, Order>,
+    price: Price,
+    // Displayed quantity, hidden iceberg reserve is tracked separately
+    quantity: Quantity,
+    hidden_quantity: Quantity,
+    order_count: usize,
+    head: Option<usize>,
+    tail: Option<usize>,
 }
 
 impl PriceLevel {
-    pub fn new(price_level: f32) -> Self {
+    pub fn new(price_level: Price) -> Self {
         PriceLevel {
             price: price_level,
-            quantity: 0.0,
-            orders: BTreeMap::new(),
+            quantity: Quantity::ZERO,
+            hidden_quantity: Quantity::ZERO,
+            order_count: 0,
+            head: None,
+            tail: None,
         }
     }
 
-    pub fn peek_next_order(&mut self) -> Option<&mut Order> {
-        match self.orders.first_entry() {
-            Some(entry) => Some(entry.into_mut()),
-            None => None,
-        }
+    // Slab key of the order at the front of the queue
+    pub fn next_order(&self) -> Option<usize> {
+        self.head
+    }
+
+    // Orders in queue order, the next order to be matched first
+    pub fn orders<'a>(&'a self, orders: &'a Slab<Order>) -> impl Iterator<Item = &'a Order> {
+        iter::successors(self.head.map(|key| &orders[key]), |order| {
+            order.next().map(|key| &orders[key])
+        })
     }
 
-    pub fn peek_last_order(&mut self) -> Option<&mut Order> {
-        match self.orders.last_entry() {
-            Some(entry) => Some(entry.into_mut()),
-            None => None,
+    // Appends an order stored in the slab to the back of the queue
+    pub fn add_order(&mut self, orders: &mut Slab<Order>, key: usize) {
+        let order = &mut orders[key];
+        order.set_previous(self.tail);
+        order.set_next(None);
+        self.add_quantity(order);
+
+        match self.tail {
+            Some(tail) => orders[tail].set_next(Some(key)),
+            None => self.head = Some(key),
         }
+
+        self.tail = Some(key);
+        self.order_count += 1;
     }
 
-    pub fn add_order(&mut self, order: Order) {
-        self.add_quantity(order.quantity());
-        self.orders.insert(order.id(), order);
+    pub fn remove_next_order(&mut self, orders: &mut Slab<Order>) -> Option<Order> {
+        let key = self.head?;
+        self.unlink(orders, key);
+        orders.remove(key)
     }
 
-    pub fn remove_next_order(&mut self) {
-        let removed_order = self.orders.pop_first();
+    // Moves the order at the front of the queue to the back under a new
+    // sequence after refreshing its displayed quantity from the reserve
+    pub fn replenish_next_order(&mut self, orders: &mut Slab<Order>, sequence: u64) {
+        if let Some(key) = self.head {
+            self.unlink(orders, key);
+            orders[key].replenish(sequence);
+            self.add_order(orders, key);
+        }
+    }
 
-        match removed_order {
-            Some((_, removed_order)) => self.remove_quantity(removed_order.quantity()),
-            None => {}
+    // Reduces the quantity of the order at the front of the queue
+    pub fn fill_next_order(&mut self, orders: &mut Slab<Order>, quantity: Quantity) {
+        if let Some(key) = self.head {
+            orders[key].remove_quantity(quantity);
+            self.quantity -= quantity;
         }
     }
 
-    pub fn cancel_order(&mut self, order_id: u64) {
-        let removed = self.orders.remove(&order_id);
+    // Reduces the quantity of a queued order without changing its position
+    pub fn reduce_order(&mut self, orders: &mut Slab<Order>, key: usize, quantity: Quantity) {
+        let order = &mut orders[key];
+        self.remove_quantity(order);
+        order.reduce_quantity(quantity);
+        self.add_quantity(order);
+    }
 
-        if removed.is_some() {
-            self.remove_quantity(removed.unwrap().quantity())
-        }
+    // The key must belong to an order queued at this level
+    pub fn cancel_order(&mut self, orders: &mut Slab<Order>, key: usize) -> Option<Order> {
+        orders.get(key)?;
+        self.unlink(orders, key);
+        orders.remove(key)
     }
 
-    pub fn quantity(&self) -> f32 {
+    pub fn quantity(&self) -> Quantity {
         self.quantity
     }
 
-    pub fn price(&self) -> f32 {
+    pub fn order_count(&self) -> usize {
+        self.order_count
+    }
+
+    pub fn hidden_quantity(&self) -> Quantity {
+        self.hidden_quantity
+    }
+
+    pub fn price(&self) -> Price {
         self.price
     }
 
-    fn add_quantity(&mut self, quantity: f32) {
-        self.quantity += quantity;
+    // Takes an order out of the queue, leaving it in the slab
+    fn unlink(&mut self, orders: &mut Slab<Order>, key: usize) {
+        let order = &orders[key];
+        let (previous, next) = (order.previous(), order.next());
+        self.remove_quantity(order);
+
+        match previous {
+            Some(previous) => orders[previous].set_next(next),
+            None => self.head = next,
+        }
+
+        match next {
+            Some(next) => orders[next].set_previous(previous),
+            None => self.tail = previous,
+        }
+
+        self.order_count -= 1;
+    }
+
+    fn add_quantity(&mut self, order: &Order) {
+        self.quantity += order.displayed_quantity();
+        self.hidden_quantity += order.hidden_quantity();
     }
 
-    fn remove_quantity(&mut self, quantity: f32) {
-        self.quantity -= quantity;
+    fn remove_quantity(&mut self, order: &Order) {
+        self.quantity -= order.displayed_quantity();
+        self.hidden_quantity -= order.hidden_quantity();
     }
 }
@@ -1,81 +1,54 @@
-use std::hash::{Hash, Hasher};
+use super::price::Price;
+use std::cmp::Ordering;
 
 // Ascending by price
-#[derive(Debug)]
-pub struct PriceLevelKeyAsk(f32);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PriceLevelKeyAsk(Price);
 
 impl PriceLevelKeyAsk {
-    pub fn new(price: f32) -> Self {
+    pub fn new(price: Price) -> Self {
         PriceLevelKeyAsk(price)
     }
 
-    pub fn get_price(&self) -> f32 {
+    pub fn get_price(&self) -> Price {
         self.0
     }
 }
 
 impl PartialOrd for PriceLevelKeyAsk {
-    fn partial_cmp(&self, other: &PriceLevelKeyAsk) -> Option<std::cmp::Ordering> {
-        self.0.partial_cmp(&other.0)
+    fn partial_cmp(&self, other: &PriceLevelKeyAsk) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for PriceLevelKeyAsk {
-    fn cmp(&self, other: &PriceLevelKeyAsk) -> std::cmp::Ordering {
-        self.0.total_cmp(&other.0)
-    }
-}
-
-impl PartialEq for PriceLevelKeyAsk {
-    fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0
-    }
-}
-
-impl Eq for PriceLevelKeyAsk {}
-
-impl Hash for PriceLevelKeyAsk {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        state.write_u32(self.0.to_bits());
+    fn cmp(&self, other: &PriceLevelKeyAsk) -> Ordering {
+        self.0.cmp(&other.0)
     }
 }
 
 // Descending by price
-#[derive(Debug)]
-pub struct PriceLevelKeyBid(f32);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PriceLevelKeyBid(Price);
 
 impl PriceLevelKeyBid {
-    pub fn new(price: f32) -> Self {
+    pub fn new(price: Price) -> Self {
         PriceLevelKeyBid(price)
     }
 
-    pub fn get_price(&self) -> f32 {
+    pub fn get_price(&self) -> Price {
         self.0
     }
 }
 
 impl PartialOrd for PriceLevelKeyBid {
-    fn partial_cmp(&self, other: &PriceLevelKeyBid) -> Option<std::cmp::Ordering> {
-        other.0.partial_cmp(&self.0)
+    fn partial_cmp(&self, other: &PriceLevelKeyBid) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for PriceLevelKeyBid {
-    fn cmp(&self, other: &PriceLevelKeyBid) -> std::cmp::Ordering {
-        other.0.total_cmp(&self.0)
-    }
-}
-
-impl PartialEq for PriceLevelKeyBid {
-    fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0
-    }
-}
-
-impl Eq for PriceLevelKeyBid {}
-
-impl Hash for PriceLevelKeyBid {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        state.write_u32(self.0.to_bits());
+    fn cmp(&self, other: &PriceLevelKeyBid) -> Ordering {
+        other.0.cmp(&self.0)
     }
 }
@@ -0,0 +1,86 @@
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Sub, SubAssign};
+
+// Fixed-point quantity expressed as an integer number of lots. Markets, the
+// protocol and journals only deal in lots, the number of decimal places a lot
+// stands for is up to the caller converting with from_f64 and to_f64.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Quantity(u64);
+
+impl Quantity {
+    pub const ZERO: Quantity = Quantity(0);
+
+    pub const fn from_lots(lots: u64) -> Self {
+        Quantity(lots)
+    }
+
+    // Returns None if the value is negative or cannot be represented exactly with `scale` decimal places
+    pub fn from_f64(value: f64, scale: u32) -> Option<Self> {
+        let scaled = value * 10f64.powi(scale as i32);
+        let lots = scaled.round();
+
+        if !lots.is_finite()
+            || lots < 0.0
+            || (scaled - lots).abs() > 1e-9 * scaled.abs().max(1.0)
+            || lots > u64::MAX as f64
+        {
+            return None;
+        }
+
+        Some(Quantity(lots as u64))
+    }
+
+    pub fn lots(&self) -> u64 {
+        self.0
+    }
+
+    pub fn to_f64(&self, scale: u32) -> f64 {
+        self.0 as f64 / 10f64.powi(scale as i32)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn checked_add(self, other: Quantity) -> Option<Quantity> {
+        self.0.checked_add(other.0).map(Quantity)
+    }
+
+    pub fn saturating_add(self, other: Quantity) -> Quantity {
+        Quantity(self.0.saturating_add(other.0))
+    }
+}
+
+impl Add for Quantity {
+    type Output = Quantity;
+
+    fn add(self, other: Quantity) -> Quantity {
+        Quantity(self.0 + other.0)
+    }
+}
+
+impl AddAssign for Quantity {
+    fn add_assign(&mut self, other: Quantity) {
+        self.0 += other.0;
+    }
+}
+
+impl Sub for Quantity {
+    type Output = Quantity;
+
+    fn sub(self, other: Quantity) -> Quantity {
+        Quantity(self.0 - other.0)
+    }
+}
+
+impl SubAssign for Quantity {
+    fn sub_assign(&mut self, other: Quantity) {
+        self.0 -= other.0;
+    }
+}
+
+impl Sum for Quantity {
+    fn sum<I: Iterator<Item = Quantity>>(iter: I) -> Quantity {
+        iter.fold(Quantity::ZERO, |total, quantity| total + quantity)
+    }
+}
@@ -0,0 +1,127 @@
+use super::order::{OrderRequest, OrderSide};
+use super::price::Price;
+use super::price_level_key::*;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StopOrder {
+    id: u64,
+    request: OrderRequest,
+}
+
+impl StopOrder {
+    pub fn new(id: u64, request: OrderRequest) -> Self {
+        StopOrder { id, request }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn request(&self) -> &OrderRequest {
+        &self.request
+    }
+}
+
+// Stop orders waiting for the last traded price to reach their stop price.
+// Within a side orders trigger closest stop price first, then by id.
+#[derive(Debug, Default)]
+pub struct StopBook {
+    // Buy stops trigger at or above their stop price
+    buy_stops: BTreeMap<(PriceLevelKeyAsk, u64), StopOrder>,
+    // Sell stops trigger at or below their stop price
+    sell_stops: BTreeMap<(PriceLevelKeyBid, u64), StopOrder>,
+    index: HashMap<u64, (OrderSide, Price)>,
+}
+
+impl StopBook {
+    pub fn new() -> Self {
+        StopBook::default()
+    }
+
+    pub fn contains(&self, id: u64) -> bool {
+        self.index.contains_key(&id)
+    }
+
+    pub fn get(&self, id: u64) -> Option<&StopOrder> {
+        match *self.index.get(&id)? {
+            (OrderSide::Bid, stop_price) => {
+                self.buy_stops.get(&(PriceLevelKeyAsk::new(stop_price), id))
+            }
+            (OrderSide::Ask, stop_price) => self
+                .sell_stops
+                .get(&(PriceLevelKeyBid::new(stop_price), id)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    // Buy stops followed by sell stops, each in trigger order
+    pub fn orders(&self) -> impl Iterator<Item = &StopOrder> + '_ {
+        self.buy_stops.values().chain(self.sell_stops.values())
+    }
+
+    // Panics if the request has no stop price
+    pub fn insert(&mut self, stop: StopOrder) {
+        let side = stop.request().side();
+        let stop_price = stop.request().stop_price().unwrap();
+
+        match side {
+            OrderSide::Bid => {
+                self.buy_stops
+                    .insert((PriceLevelKeyAsk::new(stop_price), stop.id()), stop);
+            }
+            OrderSide::Ask => {
+                self.sell_stops
+                    .insert((PriceLevelKeyBid::new(stop_price), stop.id()), stop);
+            }
+        }
+
+        self.index.insert(stop.id(), (side, stop_price));
+    }
+
+    pub fn remove(&mut self, id: u64) -> Option<StopOrder> {
+        let (side, stop_price) = self.index.remove(&id)?;
+
+        match side {
+            OrderSide::Bid => self
+                .buy_stops
+                .remove(&(PriceLevelKeyAsk::new(stop_price), id)),
+            OrderSide::Ask => self
+                .sell_stops
+                .remove(&(PriceLevelKeyBid::new(stop_price), id)),
+        }
+    }
+
+    // Removes the next stop order triggered by `last_price`. When both sides
+    // have a triggered order the one entered first is returned.
+    pub fn pop_triggered(&mut self, last_price: Price) -> Option<StopOrder> {
+        let buy = self
+            .buy_stops
+            .first_key_value()
+            .filter(|((stop_price, _), _)| stop_price.get_price() <= last_price)
+            .map(|((_, id), _)| *id);
+
+        let sell = self
+            .sell_stops
+            .first_key_value()
+            .filter(|((stop_price, _), _)| stop_price.get_price() >= last_price)
+            .map(|((_, id), _)| *id);
+
+        let id = match (buy, sell) {
+            (Some(buy), Some(sell)) => buy.min(sell),
+            (Some(buy), None) => buy,
+            (None, Some(sell)) => sell,
+            (None, None) => return None,
+        };
+
+        self.remove(id)
+    }
+}
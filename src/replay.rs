@@ -0,0 +1,168 @@
+use crate::journal::{JournalEntry, JournalReader};
+use crate::matching_engine::command::{
+    encode_post_only, encode_side, encode_time_in_force, CommandResult,
+};
+use crate::matching_engine::depth::DepthLevel;
+use crate::matching_engine::error::MarketError;
+use crate::matching_engine::execution::{ExecutionReport, Fill};
+use crate::matching_engine::market::Market;
+use crate::matching_engine::order::{OrderSide, Owner};
+use crate::matching_engine::order_book::OrderBook;
+use crate::matching_engine::price::Price;
+use crate::matching_engine::quantity::Quantity;
+use std::io::{self, Read};
+
+const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+// Rolling FNV-1a hash of a market's state. Each update folds the current
+// best prices, levels, resting orders in queue order, stop orders, last
+// traded price and halt status into the running value, so equal digests
+// after the same commands mean the books went through the same states. Entry
+// times are left out as they depend on the wall clock. Unlike std's hashers
+// the value is the same on every platform and build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateDigest(u64);
+
+impl StateDigest {
+    pub fn new() -> Self {
+        StateDigest(FNV_OFFSET_BASIS)
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+
+    pub fn update<B: OrderBook>(&mut self, market: &Market<B>) {
+        self.price(market.best_bid());
+        self.price(market.best_ask());
+        self.price(market.last_trade_price());
+        self.u64(market.is_halted() as u64);
+
+        for side in [OrderSide::Bid, OrderSide::Ask] {
+            let levels: Vec<DepthLevel> = match side {
+                OrderSide::Bid => market.bid_depth().collect(),
+                OrderSide::Ask => market.ask_depth().collect(),
+            };
+
+            self.u64(levels.len() as u64);
+
+            for level in levels {
+                self.i64(level.price().ticks());
+                self.u64(level.quantity().lots());
+                self.u64(level.order_count() as u64);
+
+                for order in market.level_orders(side, level.price()) {
+                    self.u64(order.id());
+                    self.u64(order.sequence());
+                    self.u64(order.original_quantity().lots());
+                    self.u64(order.remaining_quantity().lots());
+                    self.u64(order.filled_quantity().lots());
+                    self.u64(order.displayed_quantity().lots());
+                    self.quantity(order.display_quantity());
+                    self.owner(order.owner());
+                    self.bytes(&[
+                        encode_time_in_force(order.time_in_force()),
+                        encode_post_only(order.post_only()),
+                    ]);
+                }
+            }
+        }
+
+        for stop in market.stop_orders() {
+            let request = stop.request();
+
+            self.u64(stop.id());
+            self.bytes(&[encode_side(request.side())]);
+            self.price(request.stop_price());
+            self.price(request.price());
+            self.u64(request.quantity().lots());
+            self.quantity(request.display_quantity());
+            self.owner(request.owner());
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    fn i64(&mut self, value: i64) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    // None is kept apart from any price
+    fn price(&mut self, price: Option<Price>) {
+        match price {
+            Some(price) => {
+                self.bytes(&[1]);
+                self.i64(price.ticks());
+            }
+            None => self.bytes(&[0]),
+        }
+    }
+
+    fn quantity(&mut self, quantity: Option<Quantity>) {
+        self.u64(quantity.map_or(0, |quantity| quantity.lots()));
+    }
+
+    fn owner(&mut self, owner: Owner) {
+        self.u64(owner.account());
+        self.u64(owner.trader().unwrap_or(0));
+    }
+}
+
+impl Default for StateDigest {
+    fn default() -> Self {
+        StateDigest::new()
+    }
+}
+
+// Every fill of a command, including those of stop orders it triggered
+pub fn fills(result: &Result<CommandResult, MarketError>) -> Vec<Fill> {
+    let mut fills = Vec::new();
+
+    if let Ok(CommandResult::Executed(report)) = result {
+        collect_fills(report, &mut fills);
+    }
+
+    fills
+}
+
+fn collect_fills(report: &ExecutionReport, fills: &mut Vec<Fill>) {
+    fills.extend_from_slice(report.fills());
+
+    for triggered in report.triggered() {
+        collect_fills(triggered, fills);
+    }
+}
+
+// Applies a recorded command stream to a market, usually a fresh one.
+// `step` is called after every command with its result and the digest of
+// the market at that point. Returns the final digest. The journal must have
+// been written for a market with the same id, symbol and config.
+pub fn replay<B: OrderBook, R: Read>(
+    entries: JournalReader<R>,
+    market: &mut Market<B>,
+    mut step: impl FnMut(&JournalEntry, &Result<CommandResult, MarketError>, StateDigest),
+) -> io::Result<StateDigest> {
+    entries.check_market(market)?;
+
+    let mut digest = StateDigest::new();
+
+    for entry in entries {
+        let entry = entry?;
+        let result = market.apply(entry.command());
+
+        digest.update(market);
+        step(&entry, &result, digest);
+    }
+
+    Ok(digest)
+}
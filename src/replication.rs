@@ -0,0 +1,534 @@
+use crate::codec::{invalid_data, read_frame, write_frame, Decoder, Encoder};
+use crate::journal::{self, JournalReader};
+use crate::matching_engine::command::Command;
+use crate::matching_engine::error::{ExchangeError, MarketError};
+use crate::matching_engine::exchange::Exchange;
+use std::collections::HashMap;
+use std::io::{self, BufWriter, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// A backup that takes longer than this to subscribe or to take an entry is
+// dropped rather than left to stall the primary
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// Leads every replication message, bumped whenever the message layout
+// changes. A peer speaking another version is disconnected.
+pub const REPLICATION_VERSION: u8 = 1;
+
+// Entries queued for the backup before it is considered too slow and dropped
+const BACKUP_QUEUE_SIZE: usize = 65536;
+
+// Last sequence number a backup applied to one of its markets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Position {
+    pub market_id: u16,
+    pub symbol: String,
+    pub sequence: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplicationMessage {
+    // Backup to primary when it connects, one position for every market
+    Subscribe {
+        positions: Vec<Position>,
+    },
+    // Primary to backup, a command as it was journaled by the primary
+    Entry {
+        market_id: u16,
+        sequence: u64,
+        command: Command,
+    },
+    // Backup to primary once an entry has been applied
+    Ack {
+        market_id: u16,
+        sequence: u64,
+    },
+}
+
+impl ReplicationMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder.u8(REPLICATION_VERSION);
+
+        match self {
+            ReplicationMessage::Subscribe { positions } => {
+                encoder.u8(1).u16(positions.len() as u16);
+
+                for position in positions.iter() {
+                    encoder
+                        .u16(position.market_id)
+                        .string(&position.symbol)
+                        .u64(position.sequence);
+                }
+            }
+            ReplicationMessage::Entry {
+                market_id,
+                sequence,
+                command,
+            } => {
+                encoder.u8(2).u16(*market_id).u64(*sequence);
+                command.encode(&mut encoder);
+            }
+            ReplicationMessage::Ack {
+                market_id,
+                sequence,
+            } => {
+                encoder.u8(3).u16(*market_id).u64(*sequence);
+            }
+        }
+
+        encoder.into_bytes()
+    }
+
+    pub fn decode(bytes: &[u8]) -> io::Result<ReplicationMessage> {
+        let mut decoder = Decoder::new(bytes);
+
+        if decoder.u8()? != REPLICATION_VERSION {
+            return Err(invalid_data("unsupported replication version"));
+        }
+
+        let message = match decoder.u8()? {
+            1 => ReplicationMessage::Subscribe {
+                positions: (0..decoder.u16()?)
+                    .map(|_| {
+                        Ok(Position {
+                            market_id: decoder.u16()?,
+                            symbol: decoder.string()?,
+                            sequence: decoder.u64()?,
+                        })
+                    })
+                    .collect::<io::Result<_>>()?,
+            },
+            2 => ReplicationMessage::Entry {
+                market_id: decoder.u16()?,
+                sequence: decoder.u64()?,
+                command: Command::decode(&mut decoder)?,
+            },
+            3 => ReplicationMessage::Ack {
+                market_id: decoder.u16()?,
+                sequence: decoder.u64()?,
+            },
+            _ => return Err(invalid_data("unknown replication message type")),
+        };
+
+        decoder.finish()?;
+        Ok(message)
+    }
+}
+
+// Streams the journals of a primary's markets to a single backup over TCP.
+// A backup that connects is first caught up from the journal files, then
+// receives every command as its market journals it. Entries are queued for
+// a writer thread so a slow backup never holds up matching. Commands are not held
+// back until the backup has them, so on failover the backup can miss the
+// commands it had not acknowledged yet.
+pub struct Primary {
+    listener: TcpListener,
+    directory: PathBuf,
+    // Symbol of each market by market id
+    symbols: HashMap<u16, String>,
+    publisher: Publisher,
+}
+
+// Handle markets send their journaled commands through, see Market::set_publisher
+#[derive(Debug, Clone, Default)]
+pub struct Publisher {
+    state: Arc<Mutex<PublisherState>>,
+}
+
+#[derive(Debug, Default)]
+struct PublisherState {
+    backup: Option<Subscriber>,
+    // Counts backup connections so a stale one never drops its successor
+    connection: u64,
+    // Last sequence number acknowledged by the backup by market id
+    acknowledged: HashMap<u16, u64>,
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    entries: SyncSender<Vec<u8>>,
+    stream: TcpStream,
+}
+
+impl Primary {
+    // Listens for a backup of the markets of an exchange, journaled to `directory`
+    pub fn bind<A: ToSocketAddrs>(
+        address: A,
+        directory: impl Into<PathBuf>,
+        exchange: &Exchange,
+    ) -> io::Result<Self> {
+        let symbols = exchange
+            .symbols()
+            .filter_map(|symbol| exchange.market(symbol))
+            .map(|market| (market.market_id(), market.symbol().to_string()))
+            .collect();
+
+        Ok(Primary {
+            listener: TcpListener::bind(address)?,
+            directory: directory.into(),
+            symbols,
+            publisher: Publisher::default(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn publisher(&self) -> Publisher {
+        self.publisher.clone()
+    }
+
+    // Accepts backups until the listener fails, a new backup replaces the
+    // current one
+    pub fn run(&self) -> io::Result<()> {
+        thread::scope(|scope| {
+            for stream in self.listener.incoming() {
+                let stream = stream?;
+                stream.set_nodelay(true)?;
+
+                // a backup that fails to subscribe only ends its own connection
+                let (connection, entries) = match self.subscribe(&stream) {
+                    Ok(subscription) => subscription,
+                    Err(_) => {
+                        let _ = stream.shutdown(Shutdown::Both);
+                        continue;
+                    }
+                };
+                let writer = stream.try_clone()?;
+
+                scope.spawn(move || write_entries(writer, entries));
+                scope.spawn(move || {
+                    let _ = self.publisher.receive_acks(&stream);
+                    self.publisher.disconnected(connection);
+                });
+            }
+
+            Ok(())
+        })
+    }
+
+    // Sends a new backup every journal entry it is missing. Entries published
+    // meanwhile wait in the backup's queue until catch up is done, an entry
+    // written to a journal during catch up may be sent twice but never skipped.
+    fn subscribe(&self, stream: &TcpStream) -> io::Result<(u64, Receiver<Vec<u8>>)> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        let frame = read_frame(&mut &*stream)?.ok_or_else(|| invalid_data("no subscription"))?;
+
+        let positions = match ReplicationMessage::decode(&frame)? {
+            ReplicationMessage::Subscribe { positions } => positions,
+            _ => return Err(invalid_data("expected a subscription")),
+        };
+
+        if positions.len() != self.symbols.len()
+            || positions
+                .iter()
+                .any(|position| self.symbols.get(&position.market_id) != Some(&position.symbol))
+        {
+            return Err(invalid_data("backup markets do not match the primary"));
+        }
+
+        let (entries, receiver) = mpsc::sync_channel(BACKUP_QUEUE_SIZE);
+        let connection = self.publisher.connect(stream, entries, &positions)?;
+
+        if let Err(error) = self.catch_up(stream, &positions) {
+            self.publisher.disconnected(connection);
+            return Err(error);
+        }
+
+        stream.set_read_timeout(None)?;
+        Ok((connection, receiver))
+    }
+
+    fn catch_up(&self, stream: &TcpStream, positions: &[Position]) -> io::Result<()> {
+        let mut writer = BufWriter::new(stream);
+
+        for position in positions.iter() {
+            let sequence = catch_up(
+                &mut writer,
+                journal::path(&self.directory, &position.symbol),
+                position,
+            )?;
+
+            if sequence < position.sequence {
+                return Err(invalid_data("backup is ahead of the primary"));
+            }
+        }
+
+        writer.flush()
+    }
+}
+
+// Writes queued entries until the backup is dropped or stops taking them
+fn write_entries(mut stream: TcpStream, entries: Receiver<Vec<u8>>) {
+    for entry in entries {
+        if write_frame(&mut stream, &entry).is_err() {
+            break;
+        }
+    }
+
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+impl Publisher {
+    // Queues a journaled command for the backup if one is connected. A backup
+    // that cannot keep up is dropped and has to subscribe again.
+    pub fn publish(&self, market_id: u16, sequence: u64, command: &Command) {
+        let mut state = self.state.lock().unwrap();
+
+        let result = match &state.backup {
+            Some(backup) => backup.entries.try_send(
+                ReplicationMessage::Entry {
+                    market_id,
+                    sequence,
+                    command: *command,
+                }
+                .encode(),
+            ),
+            None => return,
+        };
+
+        if result.is_err() {
+            Publisher::drop_backup(&mut state);
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.state.lock().unwrap().backup.is_some()
+    }
+
+    // Last sequence number of a market the backup has applied
+    pub fn acknowledged(&self, market_id: u16) -> u64 {
+        self.state
+            .lock()
+            .unwrap()
+            .acknowledged
+            .get(&market_id)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    // Closes the backup connection, a backup takes this as the loss of the primary
+    pub fn disconnect(&self) {
+        Publisher::drop_backup(&mut self.state.lock().unwrap());
+    }
+
+    // Replaces the current backup, returns the connection number of the new one
+    fn connect(
+        &self,
+        stream: &TcpStream,
+        entries: SyncSender<Vec<u8>>,
+        positions: &[Position],
+    ) -> io::Result<u64> {
+        let stream = stream.try_clone()?;
+        let mut state = self.state.lock().unwrap();
+
+        for position in positions.iter() {
+            state
+                .acknowledged
+                .insert(position.market_id, position.sequence);
+        }
+
+        state.connection += 1;
+
+        if let Some(previous) = state.backup.replace(Subscriber { entries, stream }) {
+            let _ = previous.stream.shutdown(Shutdown::Both);
+        }
+
+        Ok(state.connection)
+    }
+
+    fn receive_acks(&self, stream: &TcpStream) -> io::Result<()> {
+        while let Some(frame) = read_frame(&mut &*stream)? {
+            match ReplicationMessage::decode(&frame)? {
+                ReplicationMessage::Ack {
+                    market_id,
+                    sequence,
+                } => {
+                    self.state
+                        .lock()
+                        .unwrap()
+                        .acknowledged
+                        .insert(market_id, sequence);
+                }
+                _ => return Err(invalid_data("unexpected message from backup")),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn disconnected(&self, connection: u64) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.connection == connection {
+            Publisher::drop_backup(&mut state);
+        }
+    }
+
+    fn drop_backup(state: &mut PublisherState) {
+        if let Some(backup) = state.backup.take() {
+            let _ = backup.stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+// Writes the entries of a journal after a backup's position, returns the
+// sequence number of the last entry. A missing journal counts as empty.
+fn catch_up<W: Write>(writer: &mut W, path: PathBuf, position: &Position) -> io::Result<u64> {
+    let reader = match JournalReader::open(path) {
+        Ok(reader) => reader,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(error) => return Err(error),
+    };
+
+    let mut sequence = 0;
+
+    for entry in reader {
+        let entry = entry?;
+        sequence = entry.sequence();
+
+        if sequence > position.sequence {
+            let entry = ReplicationMessage::Entry {
+                market_id: position.market_id,
+                sequence,
+                command: entry.command(),
+            };
+
+            write_frame(writer, &entry.encode())?;
+        }
+    }
+
+    Ok(sequence)
+}
+
+// Keeps the markets of an exchange in step with a primary. The exchange must
+// list the same markets as the primary, created in the same order so they
+// share market ids. A market with a journal continues from its last
+// sequence number and keeps journaling what it applies, a market without
+// one has to start out empty.
+pub struct Backup {
+    exchange: Exchange,
+    stream: TcpStream,
+    // Symbol of each market by market id
+    symbols: HashMap<u16, String>,
+    positions: HashMap<u16, u64>,
+}
+
+impl Backup {
+    pub fn connect<A: ToSocketAddrs>(address: A, exchange: Exchange) -> io::Result<Self> {
+        let mut stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+
+        let positions: Vec<Position> = exchange
+            .symbols()
+            .filter_map(|symbol| exchange.market(symbol))
+            .map(|market| Position {
+                market_id: market.market_id(),
+                symbol: market.symbol().to_string(),
+                sequence: market.journal().map_or(0, |journal| journal.sequence()),
+            })
+            .collect();
+
+        write_frame(
+            &mut stream,
+            &ReplicationMessage::Subscribe {
+                positions: positions.clone(),
+            }
+            .encode(),
+        )?;
+
+        Ok(Backup {
+            exchange,
+            stream,
+            symbols: positions
+                .iter()
+                .map(|position| (position.market_id, position.symbol.clone()))
+                .collect(),
+            positions: positions
+                .iter()
+                .map(|position| (position.market_id, position.sequence))
+                .collect(),
+        })
+    }
+
+    // Applies commands from the primary until the connection ends, which is
+    // how the loss of the primary shows
+    pub fn run(&mut self) -> io::Result<()> {
+        while let Some(frame) = read_frame(&mut self.stream)? {
+            match ReplicationMessage::decode(&frame)? {
+                ReplicationMessage::Entry {
+                    market_id,
+                    sequence,
+                    command,
+                } => self.apply(market_id, sequence, command)?,
+                _ => return Err(invalid_data("unexpected message from primary")),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn exchange(&self) -> &Exchange {
+        &self.exchange
+    }
+
+    // Last sequence number applied to a market
+    pub fn position(&self, market_id: u16) -> u64 {
+        self.positions.get(&market_id).copied().unwrap_or(0)
+    }
+
+    // Stops following the primary and hands back the markets to serve clients with
+    pub fn promote(self) -> Exchange {
+        let _ = self.stream.shutdown(Shutdown::Both);
+        self.exchange
+    }
+
+    fn apply(&mut self, market_id: u16, sequence: u64, command: Command) -> io::Result<()> {
+        let (symbol, position) = match (
+            self.symbols.get(&market_id),
+            self.positions.get_mut(&market_id),
+        ) {
+            (Some(symbol), Some(position)) => (symbol, position),
+            _ => return Err(invalid_data("entry for an unknown market")),
+        };
+
+        // entries written during catch up can arrive twice
+        if sequence <= *position {
+            return Ok(());
+        }
+
+        if sequence != *position + 1 {
+            return Err(invalid_data(
+                "replicated sequence numbers are not contiguous",
+            ));
+        }
+
+        // the primary rejected the same commands, only a local journal failure
+        // leaves the backup behind
+        if let Err(ExchangeError::Market(MarketError::JournalFailed)) =
+            self.exchange.apply(symbol, command)
+        {
+            return Err(io::Error::other("failed to journal a replicated command"));
+        }
+
+        *position = sequence;
+
+        write_frame(
+            &mut self.stream,
+            &ReplicationMessage::Ack {
+                market_id,
+                sequence,
+            }
+            .encode(),
+        )
+    }
+}
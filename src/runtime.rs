@@ -0,0 +1,214 @@
+pub mod ring_buffer;
+
+use crate::matching_engine::command::{Command, CommandResult};
+use crate::matching_engine::error::{ExchangeError, MarketError};
+use crate::matching_engine::exchange::Exchange;
+use crate::matching_engine::market::Market;
+use ring_buffer::*;
+use std::collections::{HashMap, VecDeque};
+use std::hint;
+use std::thread::{self, JoinHandle};
+
+// Polls of an empty queue before a worker parks its thread
+const IDLE_SPINS: u32 = 1 << 10;
+
+// Outcome of a submitted command, tagged with the id it was submitted with
+#[derive(Debug)]
+pub struct EngineResult {
+    pub request_id: u64,
+    pub result: Result<CommandResult, MarketError>,
+}
+
+enum Inbound {
+    Command {
+        request_id: u64,
+        // Index of the market within its worker
+        slot: usize,
+        command: Command,
+    },
+    Shutdown,
+}
+
+struct Worker {
+    inbound: Producer<Inbound>,
+    outbound: Consumer<EngineResult>,
+    thread: JoinHandle<Vec<Market>>,
+}
+
+// Runs markets on dedicated worker threads. Every market belongs to exactly
+// one worker so each book is only touched by a single thread, and commands
+// for a symbol are processed and reported in the order they were submitted.
+// The engine itself is the only producer of commands and consumer of results.
+pub struct Engine {
+    workers: Vec<Worker>,
+    // Worker and slot of each market by symbol
+    routes: HashMap<String, (usize, usize)>,
+    // Results drained while waiting for room in a full inbound queue
+    pending: VecDeque<EngineResult>,
+    next_worker: usize,
+}
+
+impl Engine {
+    // Spreads the markets of an exchange over `threads` workers, each with
+    // queues holding `capacity` commands and results
+    pub fn start(exchange: Exchange, threads: usize, capacity: usize) -> Self {
+        let mut groups: Vec<Vec<Market>> = (0..threads.max(1)).map(|_| Vec::new()).collect();
+        let mut routes = HashMap::new();
+
+        for (index, market) in exchange.into_markets().into_iter().enumerate() {
+            let group = index % groups.len();
+            routes.insert(market.symbol().to_string(), (group, groups[group].len()));
+            groups[group].push(market);
+        }
+
+        let workers = groups
+            .into_iter()
+            .map(|markets| Engine::spawn(markets, capacity))
+            .collect();
+
+        Engine {
+            workers,
+            routes,
+            pending: VecDeque::new(),
+            next_worker: 0,
+        }
+    }
+
+    pub fn submit(
+        &mut self,
+        request_id: u64,
+        symbol: &str,
+        command: Command,
+    ) -> Result<(), ExchangeError> {
+        let (worker, slot) = *self
+            .routes
+            .get(symbol)
+            .ok_or_else(|| ExchangeError::UnknownSymbol(symbol.to_string()))?;
+
+        self.send(
+            worker,
+            Inbound::Command {
+                request_id,
+                slot,
+                command,
+            },
+        );
+
+        Ok(())
+    }
+
+    // Returns the next available result without blocking
+    pub fn poll(&mut self) -> Option<EngineResult> {
+        if let Some(result) = self.pending.pop_front() {
+            return Some(result);
+        }
+
+        for _ in 0..self.workers.len() {
+            let worker = self.next_worker;
+            self.next_worker = (self.next_worker + 1) % self.workers.len();
+
+            if let Some(result) = self.workers[worker].outbound.pop() {
+                return Some(result);
+            }
+        }
+
+        None
+    }
+
+    // Stops the workers once they have processed every submitted command and
+    // hands back the markets. Results not yet polled are dropped.
+    pub fn shutdown(mut self) -> Vec<Market> {
+        self.stop()
+    }
+
+    fn spawn(mut markets: Vec<Market>, capacity: usize) -> Worker {
+        let (inbound, mut commands) = ring_buffer(capacity);
+        let (mut results, outbound) = ring_buffer(capacity);
+
+        let thread = thread::spawn(move || {
+            let mut idle = 0;
+
+            loop {
+                match commands.pop() {
+                    Some(Inbound::Command {
+                        request_id,
+                        slot,
+                        command,
+                    }) => {
+                        idle = 0;
+
+                        let mut result = EngineResult {
+                            request_id,
+                            result: markets[slot].apply(command),
+                        };
+
+                        // wait for the engine to drain results
+                        while let Err(rejected) = results.push(result) {
+                            result = rejected;
+                            thread::yield_now();
+                        }
+                    }
+                    Some(Inbound::Shutdown) => return markets,
+                    None if idle < IDLE_SPINS => {
+                        idle += 1;
+                        hint::spin_loop();
+                    }
+                    None => thread::park(),
+                }
+            }
+        });
+
+        Worker {
+            inbound,
+            outbound,
+            thread,
+        }
+    }
+
+    fn send(&mut self, worker: usize, mut message: Inbound) {
+        loop {
+            match self.workers[worker].inbound.push(message) {
+                Ok(()) => break,
+                Err(rejected) => {
+                    message = rejected;
+
+                    // the worker may itself be waiting for room for its results
+                    while let Some(result) = self.workers[worker].outbound.pop() {
+                        self.pending.push_back(result);
+                    }
+
+                    thread::yield_now();
+                }
+            }
+        }
+
+        self.workers[worker].thread.thread().unpark();
+    }
+
+    fn stop(&mut self) -> Vec<Market> {
+        for worker in 0..self.workers.len() {
+            self.send(worker, Inbound::Shutdown);
+        }
+
+        let mut markets = Vec::new();
+
+        for mut worker in self.workers.drain(..) {
+            // keep room for results so the worker can reach the shutdown message
+            while !worker.thread.is_finished() {
+                while worker.outbound.pop().is_some() {}
+                thread::yield_now();
+            }
+
+            markets.extend(worker.thread.join().unwrap());
+        }
+
+        markets.sort_by_key(|market| market.market_id());
+        markets
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
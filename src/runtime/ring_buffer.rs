@@ -0,0 +1,126 @@
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// Keeps the producer and consumer indices on separate cache lines
+#[repr(align(64))]
+struct CachePadded<T>(T);
+
+// Bounded single-producer/single-consumer queue. The producer only writes
+// `tail` and the consumer only writes `head`, both indices grow without
+// wrapping and are masked into the slot array on access.
+struct RingBuffer<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    mask: usize,
+    head: CachePadded<AtomicUsize>,
+    tail: CachePadded<AtomicUsize>,
+}
+
+// Slots are only accessed by the side that currently owns them, which is
+// decided by the acquire/release handoff of `head` and `tail`
+unsafe impl<T: Send> Sync for RingBuffer<T> {}
+
+impl<T> Drop for RingBuffer<T> {
+    fn drop(&mut self) {
+        let head = *self.head.0.get_mut();
+        let tail = *self.tail.0.get_mut();
+
+        for index in head..tail {
+            unsafe { self.slots[index & self.mask].get_mut().assume_init_drop() };
+        }
+    }
+}
+
+pub struct Producer<T> {
+    buffer: Arc<RingBuffer<T>>,
+    // Last head seen by the producer, refreshed only when the buffer looks full
+    head: usize,
+    tail: usize,
+}
+
+pub struct Consumer<T> {
+    buffer: Arc<RingBuffer<T>>,
+    // Last tail seen by the consumer, refreshed only when the buffer looks empty
+    tail: usize,
+    head: usize,
+}
+
+unsafe impl<T: Send> Send for Producer<T> {}
+unsafe impl<T: Send> Send for Consumer<T> {}
+
+// Creates a queue holding at least `capacity` items, rounded up to a power of two
+pub fn ring_buffer<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    let capacity = capacity.max(1).next_power_of_two();
+
+    let buffer = Arc::new(RingBuffer {
+        slots: (0..capacity)
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect(),
+        mask: capacity - 1,
+        head: CachePadded(AtomicUsize::new(0)),
+        tail: CachePadded(AtomicUsize::new(0)),
+    });
+
+    (
+        Producer {
+            buffer: buffer.clone(),
+            head: 0,
+            tail: 0,
+        },
+        Consumer {
+            buffer,
+            tail: 0,
+            head: 0,
+        },
+    )
+}
+
+impl<T> Producer<T> {
+    // Hands the value back if the queue is full
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        if self.tail - self.head == self.capacity() {
+            self.head = self.buffer.head.0.load(Ordering::Acquire);
+
+            if self.tail - self.head == self.capacity() {
+                return Err(value);
+            }
+        }
+
+        let slot = &self.buffer.slots[self.tail & self.buffer.mask];
+        unsafe { (*slot.get()).write(value) };
+
+        self.tail += 1;
+        self.buffer.tail.0.store(self.tail, Ordering::Release);
+
+        Ok(())
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buffer.slots.len()
+    }
+}
+
+impl<T> Consumer<T> {
+    pub fn pop(&mut self) -> Option<T> {
+        if self.head == self.tail {
+            self.tail = self.buffer.tail.0.load(Ordering::Acquire);
+
+            if self.head == self.tail {
+                return None;
+            }
+        }
+
+        let slot = &self.buffer.slots[self.head & self.buffer.mask];
+        let value = unsafe { (*slot.get()).assume_init_read() };
+
+        self.head += 1;
+        self.buffer.head.0.store(self.head, Ordering::Release);
+
+        Some(value)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head == self.buffer.tail.0.load(Ordering::Acquire)
+    }
+}
@@ -0,0 +1,348 @@
+pub mod protocol;
+
+use crate::codec::{read_frame, write_frame};
+use crate::matching_engine::command::{Command, CommandResult};
+use crate::matching_engine::error::{ExchangeError, MarketError};
+use crate::matching_engine::exchange::Exchange;
+use crate::matching_engine::execution::ExecutionReport;
+use crate::matching_engine::order::Owner;
+use protocol::*;
+use std::collections::HashMap;
+use std::io;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+// Responses queued for a session before it is considered too slow and dropped
+const SESSION_QUEUE_SIZE: usize = 4096;
+
+// TCP order entry server, each connection is served on its own thread while
+// markets are accessed one request at a time. Responses are written by a
+// separate thread per connection so a client that stops reading never holds
+// up the markets. A connection logs on to an account before entering orders,
+// orders belong to the account rather than the connection, so they can still
+// be cancelled after a reconnect, a restart or a failover.
+pub struct Server {
+    listener: TcpListener,
+    state: Mutex<ServerState>,
+}
+
+struct Session {
+    responses: SyncSender<Vec<u8>>,
+    stream: TcpStream,
+    account: Option<u64>,
+}
+
+struct ServerState {
+    exchange: Exchange,
+    sessions: HashMap<u64, Session>,
+    // Session logged on to each account, used to deliver passive fills
+    accounts: HashMap<u64, u64>,
+    total_sessions: u64,
+}
+
+impl Server {
+    pub fn bind<A: ToSocketAddrs>(address: A, exchange: Exchange) -> io::Result<Self> {
+        Ok(Server {
+            listener: TcpListener::bind(address)?,
+            state: Mutex::new(ServerState {
+                exchange,
+                sessions: HashMap::new(),
+                accounts: HashMap::new(),
+                total_sessions: 0,
+            }),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    // Accepts connections until the listener fails
+    pub fn run(&self) -> io::Result<()> {
+        thread::scope(|scope| {
+            for stream in self.listener.incoming() {
+                let stream = stream?;
+                stream.set_nodelay(true)?;
+
+                let (responses, receiver) = mpsc::sync_channel(SESSION_QUEUE_SIZE);
+                let session_id = self.state().register(&stream, responses)?;
+                let writer = stream.try_clone()?;
+
+                scope.spawn(move || write_responses(writer, receiver));
+                scope.spawn(move || {
+                    // a failed connection only ends its own session
+                    let _ = self.serve(session_id, stream);
+                    self.state().disconnect(session_id);
+                });
+            }
+
+            Ok(())
+        })
+    }
+
+    fn serve(&self, session_id: u64, mut stream: TcpStream) -> io::Result<()> {
+        // requests are only taken once the session has logged on
+        let account = loop {
+            let frame = match read_frame(&mut stream)? {
+                Some(frame) => frame,
+                None => return Ok(()),
+            };
+
+            match Logon::decode(&frame) {
+                Ok(logon) => break logon.account,
+                Err(_) => self.state().send(
+                    session_id,
+                    &Response::Rejected {
+                        client_order_id: 0,
+                        reason: RejectReason::MalformedRequest,
+                    },
+                ),
+            }
+        };
+
+        self.state().logon(session_id, account);
+
+        while let Some(frame) = read_frame(&mut stream)? {
+            let mut state = self.state();
+
+            // a request that panics ends its own session, not the server
+            let processed =
+                panic::catch_unwind(AssertUnwindSafe(|| match Request::decode(&frame) {
+                    Ok(request) => state.process(session_id, account, request),
+                    Err(_) => state.send(
+                        session_id,
+                        &Response::Rejected {
+                            client_order_id: 0,
+                            reason: RejectReason::MalformedRequest,
+                        },
+                    ),
+                }));
+
+            if processed.is_err() {
+                return Err(io::Error::other("request processing panicked"));
+            }
+        }
+
+        Ok(())
+    }
+
+    // A panic while the lock was held has already ended its session, the
+    // remaining sessions carry on
+    fn state(&self) -> MutexGuard<'_, ServerState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// Writes queued responses until the session is dropped or the client goes away
+fn write_responses(mut stream: TcpStream, responses: Receiver<Vec<u8>>) {
+    for response in responses {
+        if write_frame(&mut stream, &response).is_err() {
+            break;
+        }
+    }
+
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+impl ServerState {
+    fn register(&mut self, stream: &TcpStream, responses: SyncSender<Vec<u8>>) -> io::Result<u64> {
+        self.total_sessions += 1;
+        self.sessions.insert(
+            self.total_sessions,
+            Session {
+                responses,
+                stream: stream.try_clone()?,
+                account: None,
+            },
+        );
+        Ok(self.total_sessions)
+    }
+
+    // A session logging on to an account already in use takes over from the
+    // previous one, which is disconnected
+    fn logon(&mut self, session_id: u64, account: u64) {
+        match self.sessions.get_mut(&session_id) {
+            Some(session) => session.account = Some(account),
+            None => return,
+        }
+
+        if let Some(previous) = self.accounts.insert(account, session_id) {
+            self.disconnect(previous);
+        }
+    }
+
+    // Dropping the session ends its writer, shutting the stream down ends its reader
+    fn disconnect(&mut self, session_id: u64) {
+        if let Some(session) = self.sessions.remove(&session_id) {
+            let _ = session.stream.shutdown(Shutdown::Both);
+
+            if let Some(account) = session.account {
+                if self.accounts.get(&account) == Some(&session_id) {
+                    self.accounts.remove(&account);
+                }
+            }
+        }
+    }
+
+    fn process(&mut self, session_id: u64, account: u64, request: Request) {
+        let client_order_id = request.client_order_id;
+
+        let (order_id, command) = match request.command {
+            Command::Cancel { order_id } | Command::Replace { order_id, .. } => {
+                (Some(order_id), request.command)
+            }
+            // orders without an account are entered for the session's, the trader is up to the client
+            Command::NewOrder(order) if order.owner().account() == 0 => {
+                let owner = match order.owner().trader() {
+                    Some(trader) => Owner::new(account).with_trader(trader),
+                    None => Owner::new(account),
+                };
+
+                (None, Command::NewOrder(order.with_owner(owner)))
+            }
+            Command::NewOrder(order) if order.owner().account() != account => {
+                return self.send(
+                    session_id,
+                    &Response::Rejected {
+                        client_order_id,
+                        reason: RejectReason::AccountMismatch,
+                    },
+                );
+            }
+            command => (None, command),
+        };
+
+        // sessions can only cancel or replace orders of their account
+        let result = match (order_id, self.exchange.market(&request.symbol)) {
+            (Some(order_id), Some(market))
+                if market
+                    .order_owner(order_id)
+                    .is_none_or(|owner| owner.account() != account) =>
+            {
+                Err(ExchangeError::Market(MarketError::UnknownOrder(order_id)))
+            }
+            _ => self.exchange.apply(&request.symbol, command),
+        };
+
+        match result {
+            Ok(CommandResult::Executed(report)) => {
+                self.send(
+                    session_id,
+                    &Response::Accepted {
+                        client_order_id,
+                        order_id: report.order_id(),
+                    },
+                );
+
+                self.report(session_id, client_order_id, &report);
+
+                // stop orders triggered by this request belong to their own accounts
+                for triggered in report.triggered() {
+                    if let Some(owner) = self.accounts.get(&triggered.owner().account()).copied() {
+                        self.report(owner, 0, triggered);
+                    }
+                }
+            }
+            Ok(CommandResult::Cancelled(order)) => {
+                self.send(
+                    session_id,
+                    &Response::Cancelled {
+                        client_order_id,
+                        order_id: order.id(),
+                        quantity: order.quantity(),
+                    },
+                );
+            }
+            // halts are not part of the order entry protocol
+            Ok(CommandResult::Halted | CommandResult::Resumed) => {}
+            Err(error) => {
+                let reason = match error {
+                    ExchangeError::Market(error) => RejectReason::Market(error),
+                    _ => RejectReason::UnknownSymbol,
+                };
+
+                self.send(
+                    session_id,
+                    &Response::Rejected {
+                        client_order_id,
+                        reason,
+                    },
+                )
+            }
+        }
+    }
+
+    // Sends the fills and cancellation of an executed order, fills and
+    // cancels of resting orders go to the sessions of their accounts
+    fn report(&mut self, session_id: u64, client_order_id: u64, report: &ExecutionReport) {
+        for fill in report.fills() {
+            self.send(
+                session_id,
+                &Response::Fill {
+                    order_id: report.order_id(),
+                    trade_id: fill.trade_id(),
+                    price: fill.price(),
+                    quantity: fill.quantity(),
+                    aggressor: true,
+                },
+            );
+
+            self.send_to_account(
+                fill.passive_owner().account(),
+                &Response::Fill {
+                    order_id: fill.passive_id(),
+                    trade_id: fill.trade_id(),
+                    price: fill.price(),
+                    quantity: fill.quantity(),
+                    aggressor: false,
+                },
+            );
+        }
+
+        // resting orders cancelled or reduced by self-trade prevention, which
+        // only ever belong to the account of the incoming order
+        for prevented in report.prevented() {
+            self.send_to_account(
+                report.owner().account(),
+                &Response::Cancelled {
+                    client_order_id: 0,
+                    order_id: prevented.id(),
+                    quantity: prevented.quantity(),
+                },
+            );
+        }
+
+        if !report.cancelled_quantity().is_zero() {
+            self.send(
+                session_id,
+                &Response::Cancelled {
+                    client_order_id,
+                    order_id: report.order_id(),
+                    quantity: report.cancelled_quantity(),
+                },
+            );
+        }
+    }
+
+    fn send_to_account(&mut self, account: u64, response: &Response) {
+        if let Some(session_id) = self.accounts.get(&account).copied() {
+            self.send(session_id, response);
+        }
+    }
+
+    // Queues a response for a session, a session that has fallen too far
+    // behind is disconnected rather than waited for
+    fn send(&mut self, session_id: u64, response: &Response) {
+        let result = match self.sessions.get(&session_id) {
+            Some(session) => session.responses.try_send(response.encode()),
+            None => return,
+        };
+
+        if let Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) = result {
+            self.disconnect(session_id);
+        }
+    }
+}
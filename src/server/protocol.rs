@@ -0,0 +1,262 @@
+use crate::codec::{invalid_data, Decoder, Encoder};
+use crate::matching_engine::command::Command;
+use crate::matching_engine::error::MarketError;
+use crate::matching_engine::price::Price;
+use crate::matching_engine::quantity::Quantity;
+use std::io;
+
+// Leads every request and response, bumped whenever the message layout
+// changes. Messages of other versions are rejected.
+pub const PROTOCOL_VERSION: u8 = 1;
+
+// First message of a session, binds it to the account its orders are entered
+// for. Account 0 stands for orders without an account and cannot log on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Logon {
+    pub account: u64,
+}
+
+// Client to server message, the command decides whether it is a new order, cancel or replace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub client_order_id: u64,
+    pub symbol: String,
+    pub command: Command,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    Market(MarketError),
+    UnknownSymbol,
+    MalformedRequest,
+    // The order names an account other than the one the session logged on to
+    AccountMismatch,
+}
+
+// Server to client message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    Accepted {
+        client_order_id: u64,
+        order_id: u64,
+    },
+    Fill {
+        order_id: u64,
+        trade_id: u64,
+        price: Price,
+        quantity: Quantity,
+        aggressor: bool,
+    },
+    Cancelled {
+        client_order_id: u64,
+        order_id: u64,
+        quantity: Quantity,
+    },
+    Rejected {
+        client_order_id: u64,
+        reason: RejectReason,
+    },
+}
+
+impl Logon {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder.u8(PROTOCOL_VERSION).u64(self.account);
+        encoder.into_bytes()
+    }
+
+    pub fn decode(bytes: &[u8]) -> io::Result<Logon> {
+        let mut decoder = Decoder::new(bytes);
+        check_version(&mut decoder)?;
+
+        let logon = Logon {
+            account: decoder.u64()?,
+        };
+
+        decoder.finish()?;
+
+        match logon.account {
+            0 => Err(invalid_data("logon without an account")),
+            _ => Ok(logon),
+        }
+    }
+}
+
+impl Request {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder
+            .u8(PROTOCOL_VERSION)
+            .u64(self.client_order_id)
+            .string(&self.symbol);
+        self.command.encode(&mut encoder);
+        encoder.into_bytes()
+    }
+
+    pub fn decode(bytes: &[u8]) -> io::Result<Request> {
+        let mut decoder = Decoder::new(bytes);
+        check_version(&mut decoder)?;
+
+        let request = Request {
+            client_order_id: decoder.u64()?,
+            symbol: decoder.string()?,
+            command: Command::decode(&mut decoder)?,
+        };
+
+        decoder.finish()?;
+
+        // halting and resuming markets is left to the operator
+        match request.command {
+            Command::Halt | Command::Resume => Err(invalid_data("not an order entry command")),
+            _ => Ok(request),
+        }
+    }
+}
+
+impl RejectReason {
+    // Protocol reject code, followed on the wire by the order id for unknown orders
+    pub fn code(&self) -> u16 {
+        match self {
+            RejectReason::Market(MarketError::InvalidPrice) => 1,
+            RejectReason::Market(MarketError::InvalidTick) => 2,
+            RejectReason::Market(MarketError::InvalidQuantity) => 3,
+            RejectReason::Market(MarketError::ZeroQuantity) => 4,
+            RejectReason::Market(MarketError::UnknownOrder(_)) => 5,
+            RejectReason::Market(MarketError::MarketHalted) => 6,
+            RejectReason::Market(MarketError::SelfTrade) => 7,
+            RejectReason::Market(MarketError::RiskRejected) => 8,
+            RejectReason::Market(MarketError::WouldTakeLiquidity) => 9,
+            RejectReason::Market(MarketError::PriceOutOfRange) => 10,
+            RejectReason::Market(MarketError::JournalFailed) => 11,
+            RejectReason::Market(MarketError::OrderIdsExhausted) => 12,
+            RejectReason::Market(MarketError::LevelFull) => 13,
+            RejectReason::Market(MarketError::InvalidConfig) => 14,
+            RejectReason::UnknownSymbol => 100,
+            RejectReason::MalformedRequest => 101,
+            RejectReason::AccountMismatch => 102,
+        }
+    }
+
+    fn detail(&self) -> u64 {
+        match self {
+            RejectReason::Market(MarketError::UnknownOrder(order_id)) => *order_id,
+            _ => 0,
+        }
+    }
+
+    fn from_code(code: u16, detail: u64) -> io::Result<RejectReason> {
+        match code {
+            1 => Ok(RejectReason::Market(MarketError::InvalidPrice)),
+            2 => Ok(RejectReason::Market(MarketError::InvalidTick)),
+            3 => Ok(RejectReason::Market(MarketError::InvalidQuantity)),
+            4 => Ok(RejectReason::Market(MarketError::ZeroQuantity)),
+            5 => Ok(RejectReason::Market(MarketError::UnknownOrder(detail))),
+            6 => Ok(RejectReason::Market(MarketError::MarketHalted)),
+            7 => Ok(RejectReason::Market(MarketError::SelfTrade)),
+            8 => Ok(RejectReason::Market(MarketError::RiskRejected)),
+            9 => Ok(RejectReason::Market(MarketError::WouldTakeLiquidity)),
+            10 => Ok(RejectReason::Market(MarketError::PriceOutOfRange)),
+            11 => Ok(RejectReason::Market(MarketError::JournalFailed)),
+            12 => Ok(RejectReason::Market(MarketError::OrderIdsExhausted)),
+            13 => Ok(RejectReason::Market(MarketError::LevelFull)),
+            14 => Ok(RejectReason::Market(MarketError::InvalidConfig)),
+            100 => Ok(RejectReason::UnknownSymbol),
+            101 => Ok(RejectReason::MalformedRequest),
+            102 => Ok(RejectReason::AccountMismatch),
+            _ => Err(invalid_data("unknown reject code")),
+        }
+    }
+}
+
+impl Response {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder.u8(PROTOCOL_VERSION);
+
+        match *self {
+            Response::Accepted {
+                client_order_id,
+                order_id,
+            } => {
+                encoder.u8(1).u64(client_order_id).u64(order_id);
+            }
+            Response::Fill {
+                order_id,
+                trade_id,
+                price,
+                quantity,
+                aggressor,
+            } => {
+                encoder
+                    .u8(2)
+                    .u64(order_id)
+                    .u64(trade_id)
+                    .i64(price.ticks())
+                    .u64(quantity.lots())
+                    .u8(aggressor as u8);
+            }
+            Response::Cancelled {
+                client_order_id,
+                order_id,
+                quantity,
+            } => {
+                encoder
+                    .u8(3)
+                    .u64(client_order_id)
+                    .u64(order_id)
+                    .u64(quantity.lots());
+            }
+            Response::Rejected {
+                client_order_id,
+                reason,
+            } => {
+                encoder
+                    .u8(4)
+                    .u64(client_order_id)
+                    .u16(reason.code())
+                    .u64(reason.detail());
+            }
+        }
+
+        encoder.into_bytes()
+    }
+
+    pub fn decode(bytes: &[u8]) -> io::Result<Response> {
+        let mut decoder = Decoder::new(bytes);
+        check_version(&mut decoder)?;
+
+        let response = match decoder.u8()? {
+            1 => Response::Accepted {
+                client_order_id: decoder.u64()?,
+                order_id: decoder.u64()?,
+            },
+            2 => Response::Fill {
+                order_id: decoder.u64()?,
+                trade_id: decoder.u64()?,
+                price: Price::from_ticks(decoder.i64()?),
+                quantity: Quantity::from_lots(decoder.u64()?),
+                aggressor: decoder.u8()? != 0,
+            },
+            3 => Response::Cancelled {
+                client_order_id: decoder.u64()?,
+                order_id: decoder.u64()?,
+                quantity: Quantity::from_lots(decoder.u64()?),
+            },
+            4 => Response::Rejected {
+                client_order_id: decoder.u64()?,
+                reason: RejectReason::from_code(decoder.u16()?, decoder.u64()?)?,
+            },
+            _ => return Err(invalid_data("unknown response type")),
+        };
+
+        decoder.finish()?;
+        Ok(response)
+    }
+}
+
+fn check_version(decoder: &mut Decoder) -> io::Result<()> {
+    match decoder.u8()? {
+        PROTOCOL_VERSION => Ok(()),
+        _ => Err(invalid_data("unsupported protocol version")),
+    }
+}
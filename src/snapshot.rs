@@ -0,0 +1,414 @@
+use crate::codec::{crc32, invalid_data, Decoder, Encoder};
+use crate::journal;
+use crate::matching_engine::command::*;
+use crate::matching_engine::market::{Market, MarketConfig};
+use crate::matching_engine::order::{Order, OrderView, SelfTradePrevention};
+use crate::matching_engine::order_book::OrderBook;
+use crate::matching_engine::price::Price;
+use crate::matching_engine::quantity::Quantity;
+use crate::matching_engine::stop_book::StopOrder;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+const MAGIC: &[u8; 4] = b"TMSN";
+
+// Bumped whenever the snapshot layout changes, older versions are not read
+pub const SNAPSHOT_VERSION: u16 = 1;
+
+// Complete state of a market after the journal record with sequence number
+// `sequence`. Resting orders are stored bids then asks, best price first and
+// in queue order within a level, the order index and levels are rebuilt
+// from them on restore.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarketSnapshot {
+    pub(crate) sequence: u64,
+    pub(crate) symbol: String,
+    pub(crate) market_id: u16,
+    pub(crate) config: MarketConfig,
+    pub(crate) total_orders: u64,
+    pub(crate) total_trades: u64,
+    pub(crate) total_sequence: u64,
+    pub(crate) halted: bool,
+    pub(crate) last_trade_price: Option<Price>,
+    pub(crate) orders: Vec<OrderView>,
+    pub(crate) stops: Vec<StopOrder>,
+}
+
+impl MarketSnapshot {
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn market_id(&self) -> u16 {
+        self.market_id
+    }
+
+    pub fn orders(&self) -> &[OrderView] {
+        &self.orders
+    }
+
+    pub fn stops(&self) -> &[StopOrder] {
+        &self.stops
+    }
+
+    // Magic bytes, version, then a length and CRC-32 protected body
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Encoder::new();
+
+        body.u64(self.sequence)
+            .string(&self.symbol)
+            .u16(self.market_id);
+        encode_config(&self.config, &mut body);
+        body.u64(self.total_orders)
+            .u64(self.total_trades)
+            .u64(self.total_sequence)
+            .u8(self.halted as u8)
+            .i64(self.last_trade_price.map_or(0, |price| price.ticks()))
+            .u64(self.orders.len() as u64);
+
+        for order in self.orders.iter() {
+            encode_order(order, &mut body);
+        }
+
+        body.u64(self.stops.len() as u64);
+
+        for stop in self.stops.iter() {
+            body.u64(stop.id());
+            encode_order_request(stop.request(), &mut body);
+        }
+
+        let body = body.into_bytes();
+
+        let mut header = Encoder::new();
+        header
+            .u16(SNAPSHOT_VERSION)
+            .u32(body.len() as u32)
+            .u32(crc32(&body));
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&header.into_bytes());
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> io::Result<MarketSnapshot> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(invalid_data("not a market snapshot"));
+        }
+
+        let mut decoder = Decoder::new(&bytes[MAGIC.len()..]);
+
+        if decoder.u16()? != SNAPSHOT_VERSION {
+            return Err(invalid_data("unsupported snapshot version"));
+        }
+
+        let length = decoder.u32()? as usize;
+        let checksum = decoder.u32()?;
+        let body = &bytes[bytes.len().min(MAGIC.len() + 10)..];
+
+        if body.len() != length || crc32(body) != checksum {
+            return Err(invalid_data("snapshot checksum mismatch"));
+        }
+
+        let mut decoder = Decoder::new(body);
+
+        let sequence = decoder.u64()?;
+        let symbol = decoder.string()?;
+        let market_id = decoder.u16()?;
+
+        let config = decode_config(&mut decoder)?;
+
+        let total_orders = decoder.u64()?;
+        let total_trades = decoder.u64()?;
+        let total_sequence = decoder.u64()?;
+        let halted = decoder.u8()? != 0;
+
+        let last_trade_price = match decoder.i64()? {
+            0 => None,
+            ticks => Some(Price::from_ticks(ticks)),
+        };
+
+        let orders = (0..decoder.u64()?)
+            .map(|_| decode_order(&mut decoder))
+            .collect::<io::Result<_>>()?;
+
+        let stops = (0..decoder.u64()?)
+            .map(|_| {
+                Ok(StopOrder::new(
+                    decoder.u64()?,
+                    decode_order_request(&mut decoder)?,
+                ))
+            })
+            .collect::<io::Result<_>>()?;
+
+        decoder.finish()?;
+
+        Ok(MarketSnapshot {
+            sequence,
+            symbol,
+            market_id,
+            config,
+            total_orders,
+            total_trades,
+            total_sequence,
+            halted,
+            last_trade_price,
+            orders,
+            stops,
+        })
+    }
+}
+
+// Writes a snapshot to `directory/SYMBOL-SEQUENCE.snapshot`. The file is
+// written under a temporary name and renamed once it is on disk, so a crash
+// never leaves a partial snapshot behind.
+pub fn write<P: AsRef<Path>>(directory: P, snapshot: &MarketSnapshot) -> io::Result<PathBuf> {
+    let directory = directory.as_ref();
+    let path = directory.join(format!(
+        "{}-{:020}.snapshot",
+        snapshot.symbol, snapshot.sequence
+    ));
+    let temporary = path.with_extension("tmp");
+
+    let mut file = File::create(&temporary)?;
+    file.write_all(&snapshot.encode())?;
+    file.sync_all()?;
+    fs::rename(&temporary, &path)?;
+
+    // make the rename itself durable where directories can be synced
+    if let Ok(directory) = File::open(directory) {
+        let _ = directory.sync_all();
+    }
+
+    Ok(path)
+}
+
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<MarketSnapshot> {
+    MarketSnapshot::decode(&fs::read(path)?)
+}
+
+// Snapshot files of a symbol as (sequence, path), latest first
+pub fn list<P: AsRef<Path>>(directory: P, symbol: &str) -> io::Result<Vec<(u64, PathBuf)>> {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+
+    let prefix = format!("{}-", symbol);
+    let mut snapshots = Vec::new();
+
+    for entry in entries {
+        let path = entry?.path();
+
+        let sequence = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(&prefix))
+            .and_then(|name| name.strip_suffix(".snapshot"))
+            .and_then(|sequence| sequence.parse().ok());
+
+        if let Some(sequence) = sequence {
+            snapshots.push((sequence, path));
+        }
+    }
+
+    snapshots.sort_by(|a, b| b.cmp(a));
+    Ok(snapshots)
+}
+
+// Latest snapshot of a symbol that can be read, damaged snapshots are skipped
+pub fn latest<P: AsRef<Path>>(directory: P, symbol: &str) -> io::Result<Option<MarketSnapshot>> {
+    Ok(list(directory, symbol)?
+        .into_iter()
+        .find_map(|(_, path)| read(path).ok()))
+}
+
+// Loads the latest snapshot of the market's symbol into an empty market and
+// replays the journal entries recorded after it. Returns the sequence number
+// of the last journal entry. A journal that ends before the snapshot is an
+// error, reopening it would hand out sequence numbers the snapshot already
+// covers.
+pub fn recover<B: OrderBook, P: AsRef<Path>, Q: AsRef<Path>>(
+    market: &mut Market<B>,
+    directory: P,
+    journal_path: Q,
+) -> io::Result<u64> {
+    let sequence = match latest(directory, market.symbol())? {
+        Some(snapshot) if snapshot.market_id != market.market_id() => {
+            return Err(invalid_data("snapshot was taken under another market id"));
+        }
+        Some(snapshot) => {
+            market
+                .restore(&snapshot)
+                .map_err(|error| invalid_data(&error.to_string()))?;
+            snapshot.sequence
+        }
+        None => 0,
+    };
+
+    let last = journal::replay(journal_path, market, sequence)?;
+
+    if last < sequence {
+        return Err(invalid_data("journal ends before the latest snapshot"));
+    }
+
+    Ok(last)
+}
+
+// Periodic snapshots taken by a journaled market, only the most recent
+// `retain` snapshots are kept
+#[derive(Debug, Clone)]
+pub struct SnapshotSchedule {
+    directory: PathBuf,
+    interval: u64,
+    retain: usize,
+    last_sequence: u64,
+}
+
+impl SnapshotSchedule {
+    // Snapshots every `interval` journal records, keeping the last two
+    pub fn new(directory: impl Into<PathBuf>, interval: u64) -> Self {
+        SnapshotSchedule {
+            directory: directory.into(),
+            interval: interval.max(1),
+            retain: 2,
+            last_sequence: 0,
+        }
+    }
+
+    pub fn with_retain(mut self, retain: usize) -> Self {
+        self.retain = retain.max(1);
+        self
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn is_due(&self, sequence: u64) -> bool {
+        sequence > self.last_sequence && sequence.is_multiple_of(self.interval)
+    }
+
+    // A failed snapshot is not retried until the next interval, the journal
+    // still holds every command
+    pub fn take(&mut self, snapshot: &MarketSnapshot) -> io::Result<()> {
+        self.last_sequence = snapshot.sequence;
+        write(&self.directory, snapshot)?;
+
+        for (_, path) in list(&self.directory, &snapshot.symbol)?
+            .into_iter()
+            .skip(self.retain)
+        {
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn encode_config(config: &MarketConfig, encoder: &mut Encoder) {
+    encoder
+        .i64(config.tick_size.ticks())
+        .u64(config.lot_size.lots())
+        .u64(config.order_capacity as u64)
+        .u8(encode_self_trade_prevention(config.self_trade_prevention));
+}
+
+pub(crate) fn decode_config(decoder: &mut Decoder) -> io::Result<MarketConfig> {
+    Ok(MarketConfig {
+        tick_size: Price::from_ticks(decoder.i64()?),
+        lot_size: Quantity::from_lots(decoder.u64()?),
+        order_capacity: decoder.u64()? as usize,
+        self_trade_prevention: decode_self_trade_prevention(decoder.u8()?)?,
+    })
+}
+
+fn encode_self_trade_prevention(prevention: Option<SelfTradePrevention>) -> u8 {
+    match prevention {
+        None => 0,
+        Some(SelfTradePrevention::CancelNewest) => 1,
+        Some(SelfTradePrevention::CancelOldest) => 2,
+        Some(SelfTradePrevention::CancelBoth) => 3,
+        Some(SelfTradePrevention::DecrementAndCancel) => 4,
+    }
+}
+
+fn decode_self_trade_prevention(value: u8) -> io::Result<Option<SelfTradePrevention>> {
+    match value {
+        0 => Ok(None),
+        1 => Ok(Some(SelfTradePrevention::CancelNewest)),
+        2 => Ok(Some(SelfTradePrevention::CancelOldest)),
+        3 => Ok(Some(SelfTradePrevention::CancelBoth)),
+        4 => Ok(Some(SelfTradePrevention::DecrementAndCancel)),
+        _ => Err(invalid_data("unknown self-trade prevention")),
+    }
+}
+
+fn encode_order(order: &OrderView, encoder: &mut Encoder) {
+    let entry_time = order
+        .entry_time()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    encoder
+        .u64(order.id())
+        .u8(encode_side(order.side()))
+        .i64(order.price().ticks())
+        .u64(order.sequence())
+        .u64(order.original_quantity().lots())
+        .u64(order.remaining_quantity().lots())
+        .u64(order.filled_quantity().lots())
+        .u64(order.displayed_quantity().lots())
+        .u64(
+            order
+                .display_quantity()
+                .map_or(0, |quantity| quantity.lots()),
+        )
+        .u64(entry_time.as_secs())
+        .u32(entry_time.subsec_nanos())
+        .u8(encode_time_in_force(order.time_in_force()))
+        .u8(encode_post_only(order.post_only()));
+
+    encode_owner(order.owner(), encoder);
+}
+
+fn decode_order(decoder: &mut Decoder) -> io::Result<OrderView> {
+    let id = decoder.u64()?;
+    let side = decode_side(decoder.u8()?)?;
+    let price = Price::from_ticks(decoder.i64()?);
+    let sequence = decoder.u64()?;
+    let original_quantity = Quantity::from_lots(decoder.u64()?);
+    let remaining_quantity = Quantity::from_lots(decoder.u64()?);
+    let filled_quantity = Quantity::from_lots(decoder.u64()?);
+    let displayed_quantity = Quantity::from_lots(decoder.u64()?);
+
+    let display_quantity = match decoder.u64()? {
+        0 => None,
+        lots => Some(Quantity::from_lots(lots)),
+    };
+
+    let entry_time = UNIX_EPOCH + Duration::new(decoder.u64()?, decoder.u32()?);
+    let time_in_force = decode_time_in_force(decoder.u8()?)?;
+    let post_only = decode_post_only(decoder.u8()?)?;
+    let owner = decode_owner(decoder)?;
+
+    let order = Order::new(id, sequence, original_quantity)
+        .with_state(
+            remaining_quantity,
+            filled_quantity,
+            displayed_quantity,
+            display_quantity,
+            entry_time,
+        )
+        .with_owner(owner)
+        .with_instructions(time_in_force, post_only);
+
+    Ok(OrderView::new(side, price, &order))
+}
@@ -0,0 +1,34 @@
+// Helpers shared by the integration tests, each test binary uses a subset
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+use trade_match::matching_engine::price::Price;
+use trade_match::matching_engine::quantity::Quantity;
+
+pub fn price(value: f64) -> Price {
+    Price::from_f64(value, 2).unwrap()
+}
+
+pub fn quantity(value: f64) -> Quantity {
+    Quantity::from_f64(value, 2).unwrap()
+}
+
+// Fresh journal path unique to a test
+pub fn journal_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "trade-match-{}-{}.journal",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+// Fresh directory unique to a test
+pub fn directory(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("trade-match-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    path
+}
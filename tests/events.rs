@@ -0,0 +1,181 @@
+mod common;
+
+use common::{price, quantity};
+use std::sync::mpsc::{channel, Receiver};
+use trade_match::matching_engine::error::MarketError;
+use trade_match::matching_engine::event::*;
+use trade_match::matching_engine::market::*;
+use trade_match::matching_engine::order::{OrderRequest, OrderSide, Owner, SelfTradePrevention};
+use trade_match::matching_engine::price::Price;
+
+fn subscribe(market: &mut Market) -> Receiver<MarketEvent> {
+    let (sender, receiver) = channel();
+    market.subscribe(Box::new(sender));
+    receiver
+}
+
+#[test]
+fn test_limit_order_accepted_events() {
+    let mut market = Market::new("BTCUSD");
+    let events = subscribe(&mut market);
+
+    let order_id = market
+        .add_limit_bid(price(100.0), quantity(5.0))
+        .unwrap()
+        .order_id();
+
+    let events: Vec<MarketEvent> = events.try_iter().collect();
+
+    assert_eq!(
+        events,
+        vec![
+            MarketEvent::OrderAccepted {
+                order_id,
+                side: OrderSide::Bid,
+                price: Some(price(100.0)),
+                quantity: quantity(5.0),
+                owner: Owner::default(),
+            },
+            MarketEvent::BookTopChanged {
+                best_bid: Some(price(100.0)),
+                best_ask: None,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_match_events() {
+    let mut market = Market::new("BTCUSD");
+    let first_ask = market
+        .add_limit_ask(price(100.0), quantity(5.0))
+        .unwrap()
+        .order_id();
+    let second_ask = market
+        .add_limit_ask(price(100.0), quantity(5.0))
+        .unwrap()
+        .order_id();
+
+    let events = subscribe(&mut market);
+    let report = market.add_market_bid(quantity(7.0)).unwrap();
+    let fills = report.fills();
+
+    let events: Vec<MarketEvent> = events.try_iter().collect();
+
+    assert_eq!(
+        events[1..],
+        [
+            MarketEvent::OrderFilled {
+                order_id: first_ask,
+                fill: fills[0],
+            },
+            MarketEvent::OrderPartiallyFilled {
+                order_id: report.order_id(),
+                fill: fills[0],
+                remaining_quantity: quantity(2.0),
+            },
+            MarketEvent::OrderPartiallyFilled {
+                order_id: second_ask,
+                fill: fills[1],
+                remaining_quantity: quantity(3.0),
+            },
+            MarketEvent::OrderFilled {
+                order_id: report.order_id(),
+                fill: fills[1],
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_cancel_and_reject_events() {
+    let mut market = Market::new("BTCUSD");
+    let order_id = market
+        .add_limit_ask(price(100.0), quantity(5.0))
+        .unwrap()
+        .order_id();
+
+    let events = subscribe(&mut market);
+
+    market.cancel_limit_order(order_id).unwrap();
+    market
+        .add_limit_bid(Price::from_ticks(0), quantity(1.0))
+        .unwrap_err();
+
+    let events: Vec<MarketEvent> = events.try_iter().collect();
+
+    assert_eq!(
+        events,
+        vec![
+            MarketEvent::OrderCancelled {
+                order_id,
+                quantity: quantity(5.0),
+                reason: CancelReason::Requested,
+            },
+            MarketEvent::BookTopChanged {
+                best_bid: None,
+                best_ask: None,
+            },
+            MarketEvent::OrderRejected {
+                side: OrderSide::Bid,
+                reason: MarketError::InvalidPrice,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_unfilled_market_order_is_cancelled() {
+    let mut market = Market::new("BTCUSD");
+    let events = subscribe(&mut market);
+
+    let report = market.add_market_ask(quantity(1.0)).unwrap();
+
+    assert!(events.try_iter().any(|event| event
+        == MarketEvent::OrderCancelled {
+            order_id: report.order_id(),
+            quantity: quantity(1.0),
+            reason: CancelReason::Unfilled,
+        }));
+}
+
+#[test]
+fn test_self_trade_events() {
+    let config = MarketConfig {
+        self_trade_prevention: Some(SelfTradePrevention::DecrementAndCancel),
+        ..MarketConfig::default()
+    };
+    let mut market = Market::with_config("BTCUSD", config).unwrap();
+    let ask = market
+        .add_order(
+            OrderRequest::limit(OrderSide::Ask, price(100.0), quantity(5.0))
+                .with_owner(Owner::new(1)),
+        )
+        .unwrap();
+
+    let events = subscribe(&mut market);
+    let bid = market
+        .add_order(
+            OrderRequest::limit(OrderSide::Bid, price(100.0), quantity(2.0))
+                .with_owner(Owner::new(1)),
+        )
+        .unwrap();
+
+    let events: Vec<MarketEvent> = events.try_iter().collect();
+
+    assert_eq!(
+        events[1..],
+        [
+            MarketEvent::OrderCancelled {
+                order_id: ask.order_id(),
+                quantity: quantity(2.0),
+                reason: CancelReason::SelfTrade,
+            },
+            MarketEvent::OrderCancelled {
+                order_id: bid.order_id(),
+                quantity: quantity(2.0),
+                reason: CancelReason::SelfTrade,
+            },
+        ]
+    );
+}
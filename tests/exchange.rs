@@ -0,0 +1,156 @@
+mod common;
+
+use common::{price, quantity};
+use trade_match::matching_engine::command::{Command, CommandResult};
+use trade_match::matching_engine::error::{ExchangeError, MarketError};
+use trade_match::matching_engine::exchange::Exchange;
+use trade_match::matching_engine::market::MarketConfig;
+use trade_match::matching_engine::order::{OrderRequest, OrderSide};
+use trade_match::matching_engine::quantity::Quantity;
+
+fn exchange(symbols: &[&str]) -> Exchange {
+    let mut exchange = Exchange::new();
+
+    for symbol in symbols {
+        exchange
+            .create_market(symbol, MarketConfig::default())
+            .unwrap();
+    }
+
+    exchange
+}
+
+#[test]
+fn test_create_and_remove_markets() {
+    let mut exchange = exchange(&["AAPL", "MSFT"]);
+
+    assert_eq!(
+        exchange
+            .create_market("AAPL", MarketConfig::default())
+            .unwrap_err(),
+        ExchangeError::DuplicateSymbol("AAPL".to_string())
+    );
+
+    let mut symbols: Vec<&str> = exchange.symbols().collect();
+    symbols.sort();
+    assert_eq!(symbols, vec!["AAPL", "MSFT"]);
+
+    let removed = exchange.remove_market("AAPL").unwrap();
+    assert_eq!(removed.symbol(), "AAPL");
+    assert!(exchange.market("AAPL").is_none());
+    assert!(exchange.remove_market("AAPL").is_none());
+}
+
+#[test]
+fn test_create_market_rejects_invalid_config() {
+    let mut exchange = exchange(&["AAPL"]);
+    let config = MarketConfig {
+        lot_size: Quantity::ZERO,
+        ..MarketConfig::default()
+    };
+
+    assert_eq!(
+        exchange.create_market("MSFT", config).err(),
+        Some(ExchangeError::Market(MarketError::InvalidConfig))
+    );
+    assert!(exchange.market("MSFT").is_none());
+
+    // no market id is used up by the failed attempt
+    let market = exchange
+        .create_market("MSFT", MarketConfig::default())
+        .unwrap();
+    assert_eq!(market.market_id(), 2);
+}
+
+#[test]
+fn test_orders_are_routed_by_symbol() {
+    let mut exchange = exchange(&["AAPL", "MSFT"]);
+
+    exchange
+        .add_order(
+            "AAPL",
+            OrderRequest::limit(OrderSide::Bid, price(100.0), quantity(1.0)),
+        )
+        .unwrap();
+
+    assert_eq!(
+        exchange.market("AAPL").unwrap().best_bid(),
+        Some(price(100.0))
+    );
+    assert_eq!(exchange.market("MSFT").unwrap().best_bid(), None);
+
+    assert_eq!(
+        exchange
+            .apply("TSLA", Command::Cancel { order_id: 1 })
+            .unwrap_err(),
+        ExchangeError::UnknownSymbol("TSLA".to_string())
+    );
+}
+
+#[test]
+fn test_order_ids_are_unique_across_symbols() {
+    let mut exchange = exchange(&["AAPL", "MSFT"]);
+    let request = OrderRequest::limit(OrderSide::Ask, price(100.0), quantity(1.0));
+
+    let aapl = exchange.add_order("AAPL", request).unwrap();
+    let msft = exchange.add_order("MSFT", request).unwrap();
+
+    assert_ne!(aapl.order_id(), msft.order_id());
+    assert_eq!(exchange.symbol_of(aapl.order_id()), Some("AAPL"));
+    assert_eq!(exchange.symbol_of(msft.order_id()), Some("MSFT"));
+}
+
+#[test]
+fn test_cancel_and_modify_route_by_order_id() {
+    let mut exchange = exchange(&["AAPL", "MSFT"]);
+
+    let order = exchange
+        .add_order(
+            "MSFT",
+            OrderRequest::limit(OrderSide::Bid, price(100.0), quantity(2.0)),
+        )
+        .unwrap();
+
+    exchange
+        .modify_order(order.order_id(), price(101.0), quantity(2.0))
+        .unwrap();
+    assert_eq!(
+        exchange.market("MSFT").unwrap().best_bid(),
+        Some(price(101.0))
+    );
+
+    let cancelled = exchange.cancel_order(order.order_id()).unwrap();
+    assert_eq!(cancelled.quantity(), quantity(2.0));
+    assert!(!exchange.order_exists(order.order_id()));
+}
+
+#[test]
+fn test_orders_of_removed_market_are_unknown() {
+    let mut exchange = exchange(&["AAPL"]);
+
+    let order = exchange
+        .add_order(
+            "AAPL",
+            OrderRequest::limit(OrderSide::Bid, price(100.0), quantity(1.0)),
+        )
+        .unwrap();
+
+    exchange.remove_market("AAPL");
+    exchange
+        .create_market("AAPL", MarketConfig::default())
+        .unwrap();
+
+    assert_eq!(
+        exchange.cancel_order(order.order_id()).unwrap_err(),
+        ExchangeError::Market(MarketError::UnknownOrder(order.order_id()))
+    );
+
+    let result = exchange
+        .apply(
+            "AAPL",
+            Command::NewOrder(OrderRequest::market(OrderSide::Ask, quantity(1.0))),
+        )
+        .unwrap();
+
+    assert!(matches!(result, CommandResult::Executed(report) if report.fills().is_empty()));
+}
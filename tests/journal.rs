@@ -0,0 +1,320 @@
+mod common;
+
+use common::{journal_path, price, quantity};
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::PathBuf;
+use trade_match::journal::{self, FsyncPolicy, Journal, JournalHeader, JournalReader};
+use trade_match::matching_engine::command::Command;
+use trade_match::matching_engine::error::MarketError;
+use trade_match::matching_engine::market::{Market, MarketConfig};
+use trade_match::matching_engine::order::{OrderRequest, OrderSide, SelfTradePrevention};
+
+fn header() -> JournalHeader {
+    JournalHeader::new(0, "BTCUSD", MarketConfig::default())
+}
+
+fn commands(path: &PathBuf) -> Vec<(u64, Command)> {
+    JournalReader::open(path)
+        .unwrap()
+        .map(|entry| entry.map(|entry| (entry.sequence(), entry.command())))
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[test]
+fn test_append_and_read_back() {
+    let path = journal_path("append");
+    let new_order = Command::NewOrder(OrderRequest::limit(
+        OrderSide::Bid,
+        price(10.0),
+        quantity(1.0),
+    ));
+    let cancel = Command::Cancel { order_id: 1 };
+
+    let mut journal = Journal::open(&path, FsyncPolicy::Always, &header()).unwrap();
+
+    assert_eq!(journal.append(&new_order).unwrap(), 1);
+    assert_eq!(journal.append(&cancel).unwrap(), 2);
+
+    drop(journal);
+
+    // reopening continues the sequence
+    let mut journal = Journal::open(&path, FsyncPolicy::Every(2), &header()).unwrap();
+
+    assert_eq!(journal.sequence(), 2);
+    assert_eq!(journal.append(&cancel).unwrap(), 3);
+
+    drop(journal);
+
+    assert_eq!(
+        commands(&path),
+        vec![(1, new_order), (2, cancel), (3, cancel)]
+    );
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_torn_record_is_discarded() {
+    let path = journal_path("torn");
+    let cancel = Command::Cancel { order_id: 7 };
+
+    let mut journal = Journal::open(&path, FsyncPolicy::Never, &header()).unwrap();
+    journal.append(&cancel).unwrap();
+    journal.append(&cancel).unwrap();
+    drop(journal);
+
+    // cut the last record short, as a crash in the middle of a write would
+    let length = fs::metadata(&path).unwrap().len();
+    OpenOptions::new()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_len(length - 3)
+        .unwrap();
+
+    assert_eq!(commands(&path), vec![(1, cancel)]);
+
+    let mut journal = Journal::open(&path, FsyncPolicy::Always, &header()).unwrap();
+    assert_eq!(journal.append(&cancel).unwrap(), 2);
+    drop(journal);
+
+    assert_eq!(commands(&path), vec![(1, cancel), (2, cancel)]);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_checksum_mismatch_of_last_record_ends_journal() {
+    let path = journal_path("checksum-tail");
+    let cancel = Command::Cancel { order_id: 7 };
+
+    let mut journal = Journal::open(&path, FsyncPolicy::Always, &header()).unwrap();
+    journal.append(&cancel).unwrap();
+    journal.append(&cancel).unwrap();
+    drop(journal);
+
+    let mut bytes = fs::read(&path).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xFF;
+    fs::write(&path, &bytes).unwrap();
+
+    assert_eq!(commands(&path), vec![(1, cancel)]);
+
+    // the damaged record is replaced by the next append
+    let mut journal = Journal::open(&path, FsyncPolicy::Always, &header()).unwrap();
+    assert_eq!(journal.append(&cancel).unwrap(), 2);
+    drop(journal);
+
+    assert_eq!(commands(&path), vec![(1, cancel), (2, cancel)]);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_damaged_record_followed_by_others_is_an_error() {
+    let path = journal_path("checksum-middle");
+    let cancel = Command::Cancel { order_id: 7 };
+
+    let mut journal = Journal::open(&path, FsyncPolicy::Always, &header()).unwrap();
+    journal.append(&cancel).unwrap();
+    let first = fs::metadata(&path).unwrap().len() as usize;
+    journal.append(&cancel).unwrap();
+    journal.append(&cancel).unwrap();
+    drop(journal);
+
+    // a crash only ever damages the last record, this one has two behind it
+    let mut bytes = fs::read(&path).unwrap();
+    bytes[first - 1] ^= 0xFF;
+    fs::write(&path, &bytes).unwrap();
+
+    let entries: Vec<_> = JournalReader::open(&path).unwrap().collect();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        entries[0].as_ref().unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+
+    // the records after it are left for an operator to recover
+    let error = Journal::open(&path, FsyncPolicy::Always, &header()).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(fs::read(&path).unwrap(), bytes);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_market_rebuilt_from_journal() {
+    let path = journal_path("market");
+
+    let mut market = Market::new("BTCUSD");
+    let header = JournalHeader::from_market(&market);
+    market.set_journal(Journal::open(&path, FsyncPolicy::Always, &header).unwrap());
+
+    let bid = market.add_limit_bid(price(10.0), quantity(5.0)).unwrap();
+    market.add_limit_bid(price(9.0), quantity(2.0)).unwrap();
+    market.add_limit_ask(price(11.0), quantity(3.0)).unwrap();
+    market
+        .add_order(OrderRequest::stop_market(
+            OrderSide::Ask,
+            price(9.5),
+            quantity(1.0),
+        ))
+        .unwrap();
+    market.add_market_ask(quantity(1.0)).unwrap();
+    market
+        .modify_order(bid.order_id(), price(10.5), quantity(3.0))
+        .unwrap();
+    assert_eq!(
+        market.cancel_limit_order(999).unwrap_err(),
+        MarketError::UnknownOrder(999)
+    );
+    market.add_market_ask(quantity(4.0)).unwrap();
+
+    assert_eq!(market.journal().unwrap().sequence(), 8);
+
+    let mut rebuilt = Market::new("BTCUSD");
+
+    assert_eq!(journal::replay(&path, &mut rebuilt, 0).unwrap(), 8);
+    assert_eq!(rebuilt.depth(usize::MAX), market.depth(usize::MAX));
+    assert_eq!(rebuilt.last_trade_price(), market.last_trade_price());
+
+    // the ask at 11.00 is the only order left resting
+    for id in 1..=6 {
+        let order = |market: &Market| {
+            market
+                .get_order(id)
+                .map(|order| (order.price(), order.remaining_quantity()))
+        };
+
+        assert_eq!(order(&rebuilt), order(&market));
+    }
+
+    assert_eq!(
+        rebuilt.get_order(3).unwrap().remaining_quantity(),
+        quantity(3.0)
+    );
+
+    drop(market);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_halts_are_journaled() {
+    let path = journal_path("halt");
+
+    let mut market = Market::new("BTCUSD");
+    let header = JournalHeader::from_market(&market);
+    market.set_journal(Journal::open(&path, FsyncPolicy::Always, &header).unwrap());
+
+    market.halt().unwrap();
+    assert_eq!(
+        market.add_limit_bid(price(10.0), quantity(1.0)),
+        Err(MarketError::MarketHalted)
+    );
+    market.resume().unwrap();
+    market.add_limit_ask(price(11.0), quantity(1.0)).unwrap();
+    market.halt().unwrap();
+
+    assert_eq!(commands(&path)[0], (1, Command::Halt));
+    assert_eq!(commands(&path)[2], (3, Command::Resume));
+
+    // the bid rejected while halted is rejected again on replay
+    let mut rebuilt = Market::new("BTCUSD");
+
+    assert_eq!(journal::replay(&path, &mut rebuilt, 0).unwrap(), 5);
+    assert_eq!(rebuilt.best_bid(), None);
+    assert_eq!(rebuilt.depth(usize::MAX), market.depth(usize::MAX));
+    assert!(rebuilt.is_halted());
+
+    drop(market);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_header_is_read_back() {
+    let path = journal_path("header");
+    let config = MarketConfig {
+        tick_size: price(0.05),
+        self_trade_prevention: Some(SelfTradePrevention::CancelNewest),
+        ..MarketConfig::default()
+    };
+    let header = JournalHeader::new(3, "AAPL", config);
+
+    drop(Journal::open(&path, FsyncPolicy::Always, &header).unwrap());
+
+    let reader = JournalReader::open(&path).unwrap();
+    assert_eq!(reader.header(), &header);
+    assert_eq!(reader.header().market_id(), 3);
+    assert_eq!(reader.header().symbol(), "AAPL");
+    assert_eq!(reader.header().config(), &config);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_torn_header_starts_over() {
+    let path = journal_path("torn-header");
+
+    drop(Journal::open(&path, FsyncPolicy::Always, &header()).unwrap());
+
+    // a crash while the header was written leaves part of it
+    let length = fs::metadata(&path).unwrap().len();
+    OpenOptions::new()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_len(length - 1)
+        .unwrap();
+
+    assert_eq!(
+        JournalReader::open(&path).unwrap_err().kind(),
+        io::ErrorKind::UnexpectedEof
+    );
+
+    let mut journal = Journal::open(&path, FsyncPolicy::Always, &header()).unwrap();
+    assert_eq!(journal.append(&Command::Halt).unwrap(), 1);
+    drop(journal);
+
+    assert_eq!(commands(&path), vec![(1, Command::Halt)]);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_journal_of_another_market_is_refused() {
+    let path = journal_path("other-market");
+
+    let mut journal = Journal::open(&path, FsyncPolicy::Always, &header()).unwrap();
+    journal.append(&Command::Halt).unwrap();
+    drop(journal);
+
+    let bytes = fs::read(&path).unwrap();
+    let other = JournalHeader::new(1, "BTCUSD", MarketConfig::default());
+
+    let error = Journal::open(&path, FsyncPolicy::Always, &other).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(fs::read(&path).unwrap(), bytes);
+
+    // nor is it replayed into a market with another config
+    let mut market = Market::with_config(
+        "BTCUSD",
+        MarketConfig {
+            lot_size: quantity(0.10),
+            ..MarketConfig::default()
+        },
+    )
+    .unwrap();
+
+    let error = journal::replay(&path, &mut market, 0).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(!market.is_halted());
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_crc32_check_value() {
+    assert_eq!(trade_match::codec::crc32(b"123456789"), 0xCBF4_3926);
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use common::{price, quantity};
+use trade_match::matching_engine::error::MarketError;
+use trade_match::matching_engine::ladder_book::LadderBook;
+use trade_match::matching_engine::market::{Market, MarketConfig};
+use trade_match::matching_engine::order::{OrderRequest, OrderSide, PostOnly};
+use trade_match::matching_engine::order_book::OrderBook;
+use trade_match::matching_engine::price::Price;
+
+// Market holding prices from 1.00 to 20.00
+fn ladder_market() -> Market<LadderBook> {
+    let config = MarketConfig::default();
+    let book = LadderBook::new(price(1.0), price(20.0), config.tick_size);
+
+    Market::with_book(0, "BTCUSD", config, book).unwrap()
+}
+
+#[test]
+fn test_best_prices_across_bitmap_words() {
+    let mut market = ladder_market();
+
+    market.add_limit_bid(price(1.05), quantity(1.0)).unwrap();
+    market.add_limit_bid(price(4.70), quantity(1.0)).unwrap();
+    market.add_limit_ask(price(19.99), quantity(1.0)).unwrap();
+    market.add_limit_ask(price(6.40), quantity(1.0)).unwrap();
+
+    assert_eq!(market.best_bid(), Some(price(4.70)));
+    assert_eq!(market.best_ask(), Some(price(6.40)));
+    assert_eq!(market.level_count(OrderSide::Bid), 2);
+
+    let bids: Vec<Price> = market.bid_depth().map(|level| level.price()).collect();
+    let asks: Vec<Price> = market.ask_depth().map(|level| level.price()).collect();
+
+    assert_eq!(bids, vec![price(4.70), price(1.05)]);
+    assert_eq!(asks, vec![price(6.40), price(19.99)]);
+
+    let report = market.add_limit_bid(price(20.0), quantity(2.5)).unwrap();
+
+    assert_eq!(report.fills().len(), 2);
+    assert_eq!(report.fills()[1].price(), price(19.99));
+    assert_eq!(market.best_ask(), None);
+    assert_eq!(market.best_bid(), Some(price(20.0)));
+    assert_eq!(market.level_count(OrderSide::Ask), 0);
+}
+
+#[test]
+fn test_cancel_clears_level_bit() {
+    let mut market = ladder_market();
+
+    let first = market.add_limit_ask(price(10.0), quantity(1.0)).unwrap();
+    market.add_limit_ask(price(12.0), quantity(1.0)).unwrap();
+
+    market.cancel_limit_order(first.order_id()).unwrap();
+
+    assert_eq!(market.best_ask(), Some(price(12.0)));
+    assert_eq!(market.level_count(OrderSide::Ask), 1);
+    assert!(market.book().level(OrderSide::Ask, price(10.0)).is_none());
+}
+
+#[test]
+fn test_rejects_prices_outside_band() {
+    let mut market = ladder_market();
+
+    assert_eq!(
+        market
+            .add_limit_bid(price(20.01), quantity(1.0))
+            .unwrap_err(),
+        MarketError::PriceOutOfRange
+    );
+    assert_eq!(
+        market
+            .add_limit_ask(price(0.99), quantity(1.0))
+            .unwrap_err(),
+        MarketError::PriceOutOfRange
+    );
+
+    // a post-only bid cannot slide below the bottom of the band
+    market.add_limit_ask(price(1.0), quantity(1.0)).unwrap();
+
+    let request = OrderRequest::limit(OrderSide::Bid, price(1.0), quantity(1.0))
+        .with_post_only(PostOnly::Slide);
+
+    assert_eq!(
+        market.add_order(request).unwrap_err(),
+        MarketError::WouldTakeLiquidity
+    );
+}
+
+#[test]
+fn test_matches_like_tree_book() {
+    let mut ladder = ladder_market();
+    let mut tree = Market::new("BTCUSD");
+
+    for i in 0..200u64 {
+        let side = match i % 3 {
+            0 => OrderSide::Ask,
+            _ => OrderSide::Bid,
+        };
+        let request = OrderRequest::limit(
+            side,
+            price(5.0 + (i * 37 % 101) as f64 / 100.0),
+            quantity(1.0 + (i % 7) as f64),
+        );
+
+        let ladder_report = ladder.add_order(request).unwrap();
+        let tree_report = tree.add_order(request).unwrap();
+
+        assert_eq!(ladder_report.fills(), tree_report.fills());
+        assert_eq!(ladder.depth(usize::MAX), tree.depth(usize::MAX));
+    }
+}
+
+#[test]
+fn test_reject_prices_between_ladder_ticks() {
+    let config = MarketConfig::default();
+    let book = LadderBook::new(price(1.0), price(2.0), price(0.05));
+    let mut market = Market::with_book(0, "BTCUSD", config, book).unwrap();
+
+    assert_eq!(
+        market.add_limit_bid(price(1.01), quantity(1.0)),
+        Err(MarketError::PriceOutOfRange)
+    );
+    assert_eq!(
+        market.add_order(OrderRequest::stop_limit(
+            OrderSide::Ask,
+            price(1.5),
+            price(1.52),
+            quantity(1.0),
+        )),
+        Err(MarketError::PriceOutOfRange)
+    );
+
+    market.add_limit_bid(price(1.05), quantity(1.0)).unwrap();
+    assert_eq!(market.best_bid(), Some(price(1.05)));
+    assert!(!market.book().contains_price(price(1.06)));
+}
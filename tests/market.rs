@@ -1,4 +1,15 @@
+mod common;
+
+use common::{price, quantity};
+use std::time::SystemTime;
+use trade_match::matching_engine::depth::DepthLevel;
+use trade_match::matching_engine::error::MarketError;
 use trade_match::matching_engine::market::*;
+use trade_match::matching_engine::order::{
+    OrderRequest, OrderSide, Owner, PostOnly, SelfTradePrevention, TimeInForce,
+};
+use trade_match::matching_engine::price::Price;
+use trade_match::matching_engine::quantity::Quantity;
 
 #[test]
 fn test_create_market() {
@@ -9,141 +20,156 @@ fn test_create_market() {
 #[test]
 fn test_initial_best_bid_and_best_ask() {
     let market = Market::new("BTCUSD");
-    assert_eq!(market.best_bid(), f32::NEG_INFINITY);
-    assert_eq!(market.best_ask(), f32::INFINITY);
+    assert_eq!(market.best_bid(), None);
+    assert_eq!(market.best_ask(), None);
 }
 
 #[test]
 fn test_add_multiple_limit_bids() {
     let mut market = Market::new("BTCUSD");
-    market.add_limit_bid(100.0, 10.0).unwrap();
-    market.add_limit_bid(101.0, 10.0).unwrap();
-    assert_eq!(market.best_bid(), 101.0);
+    market.add_limit_bid(price(100.0), quantity(10.0)).unwrap();
+    market.add_limit_bid(price(101.0), quantity(10.0)).unwrap();
+    assert_eq!(market.best_bid(), Some(price(101.0)));
 }
 
 #[test]
 fn test_add_multiple_limit_asks() {
     let mut market = Market::new("BTCUSD");
-    market.add_limit_ask(100.0, 10.0).unwrap();
-    market.add_limit_ask(99.0, 10.0).unwrap();
-    assert_eq!(market.best_ask(), 99.0);
+    market.add_limit_ask(price(100.0), quantity(10.0)).unwrap();
+    market.add_limit_ask(price(99.0), quantity(10.0)).unwrap();
+    assert_eq!(market.best_ask(), Some(price(99.0)));
 }
 
 #[test]
 fn test_execute_limit_ask() {
     let mut market = Market::new("BTCUSD");
 
-    market.add_limit_bid(101.0, 5.0).unwrap();
-    market.add_limit_bid(102.0, 5.0).unwrap();
-    market.add_limit_bid(103.0, 5.0).unwrap();
-    market.add_limit_bid(104.0, 5.0).unwrap();
+    market.add_limit_bid(price(101.0), quantity(5.0)).unwrap();
+    market.add_limit_bid(price(102.0), quantity(5.0)).unwrap();
+    market.add_limit_bid(price(103.0), quantity(5.0)).unwrap();
+    market.add_limit_bid(price(104.0), quantity(5.0)).unwrap();
 
-    assert_eq!(market.best_bid(), 104.0);
+    assert_eq!(market.best_bid(), Some(price(104.0)));
 
     // marketable order
-    market.add_limit_ask(100.0, 10.0).unwrap();
+    market.add_limit_ask(price(100.0), quantity(10.0)).unwrap();
 
-    assert_eq!(market.best_bid(), 102.0);
+    assert_eq!(market.best_bid(), Some(price(102.0)));
 }
 
 #[test]
 fn test_execute_limit_bid() {
     let mut market = Market::new("BTCUSD");
 
-    market.add_limit_ask(100.0, 5.0).unwrap();
-    market.add_limit_ask(99.0, 5.0).unwrap();
-    market.add_limit_ask(98.0, 5.0).unwrap();
-    market.add_limit_ask(97.0, 5.0).unwrap();
+    market.add_limit_ask(price(100.0), quantity(5.0)).unwrap();
+    market.add_limit_ask(price(99.0), quantity(5.0)).unwrap();
+    market.add_limit_ask(price(98.0), quantity(5.0)).unwrap();
+    market.add_limit_ask(price(97.0), quantity(5.0)).unwrap();
 
-    assert_eq!(market.best_ask(), 97.0);
+    assert_eq!(market.best_ask(), Some(price(97.0)));
 
     // marketable order
-    market.add_limit_bid(101.0, 10.0).unwrap();
+    market.add_limit_bid(price(101.0), quantity(10.0)).unwrap();
 
-    assert_eq!(market.best_ask(), 99.0);
+    assert_eq!(market.best_ask(), Some(price(99.0)));
 }
 
 #[test]
 fn test_add_market_bid() {
     let mut market = Market::new("BTCUSD");
 
-    market.add_limit_ask(99.0, 5.0).unwrap();
-    market.add_limit_ask(100.0, 5.0).unwrap();
-    market.add_limit_ask(101.0, 5.0).unwrap();
-    market.add_limit_ask(102.0, 5.0).unwrap();
-    market.add_limit_ask(103.0, 5.0).unwrap();
+    market.add_limit_ask(price(99.0), quantity(5.0)).unwrap();
+    market.add_limit_ask(price(100.0), quantity(5.0)).unwrap();
+    market.add_limit_ask(price(101.0), quantity(5.0)).unwrap();
+    market.add_limit_ask(price(102.0), quantity(5.0)).unwrap();
+    market.add_limit_ask(price(103.0), quantity(5.0)).unwrap();
 
-    assert_eq!(market.best_ask(), 99.0);
+    assert_eq!(market.best_ask(), Some(price(99.0)));
 
-    market.add_market_bid(14.99);
+    market.add_market_bid(quantity(14.99)).unwrap();
 
-    assert_eq!(market.best_ask(), 101.0);
+    assert_eq!(market.best_ask(), Some(price(101.0)));
 
-    market.add_market_bid(5.00);
+    market.add_market_bid(quantity(5.00)).unwrap();
 
-    assert_eq!(market.best_ask(), 102.0);
-    assert_eq!(market.best_bid(), f32::NEG_INFINITY);
+    assert_eq!(market.best_ask(), Some(price(102.0)));
+    assert_eq!(market.best_bid(), None);
 }
 
 #[test]
 fn test_add_market_ask() {
     let mut market = Market::new("BTCUSD");
 
-    market.add_limit_bid(10.0, 5.0).unwrap();
-    market.add_limit_bid(9.0, 5.0).unwrap();
-    market.add_limit_bid(8.0, 5.0).unwrap();
-    market.add_limit_bid(7.0, 5.0).unwrap();
-    market.add_limit_bid(6.0, 5.0).unwrap();
+    market.add_limit_bid(price(10.0), quantity(5.0)).unwrap();
+    market.add_limit_bid(price(9.0), quantity(5.0)).unwrap();
+    market.add_limit_bid(price(8.0), quantity(5.0)).unwrap();
+    market.add_limit_bid(price(7.0), quantity(5.0)).unwrap();
+    market.add_limit_bid(price(6.0), quantity(5.0)).unwrap();
 
-    assert_eq!(market.best_bid(), 10.0);
+    assert_eq!(market.best_bid(), Some(price(10.0)));
 
-    market.add_market_ask(14.99);
+    market.add_market_ask(quantity(14.99)).unwrap();
 
-    assert_eq!(market.best_bid(), 8.0);
+    assert_eq!(market.best_bid(), Some(price(8.0)));
 
-    market.add_market_ask(5.0);
+    market.add_market_ask(quantity(5.0)).unwrap();
 
-    assert_eq!(market.best_bid(), 7.0);
-    assert_eq!(market.best_ask(), f32::INFINITY);
+    assert_eq!(market.best_bid(), Some(price(7.0)));
+    assert_eq!(market.best_ask(), None);
 }
 
 #[test]
 fn test_cancel_nonexistent_order() {
     let mut market = Market::new("BTCUSD");
-    assert!(!market.cancel_limit_order(1));
+    assert_eq!(
+        market.cancel_limit_order(1).unwrap_err(),
+        MarketError::UnknownOrder(1)
+    );
 }
 
 #[test]
 fn test_cancel_existing_order() {
     let mut market = Market::new("BTCUSD");
-    let order_id = market.add_limit_bid(100.0, 10.0).unwrap();
-    assert!(market.cancel_limit_order(order_id));
+    let order_id = market
+        .add_limit_bid(price(100.0), quantity(10.0))
+        .unwrap()
+        .order_id();
+    assert!(market.cancel_limit_order(order_id).is_ok());
 }
 
 #[test]
 fn test_cancel_order_updates_best_bid() {
     let mut market = Market::new("BTCUSD");
-    let order_id = market.add_limit_bid(100.0, 10.0).unwrap();
-    market.add_limit_bid(101.0, 10.0).unwrap();
-    market.cancel_limit_order(order_id);
-    assert_eq!(market.best_bid(), 101.0);
+    let order_id = market
+        .add_limit_bid(price(100.0), quantity(10.0))
+        .unwrap()
+        .order_id();
+    market.add_limit_bid(price(101.0), quantity(10.0)).unwrap();
+    market.cancel_limit_order(order_id).unwrap();
+    assert_eq!(market.best_bid(), Some(price(101.0)));
 }
 
 #[test]
 fn test_cancel_order_updates_best_ask() {
     let mut market = Market::new("BTCUSD");
-    let order_id = market.add_limit_ask(100.0, 10.0).unwrap();
-    market.add_limit_ask(99.0, 10.0).unwrap();
-    market.cancel_limit_order(order_id);
-    assert_eq!(market.best_ask(), 99.0);
+    let order_id = market
+        .add_limit_ask(price(100.0), quantity(10.0))
+        .unwrap()
+        .order_id();
+    market.add_limit_ask(price(99.0), quantity(10.0)).unwrap();
+    market.cancel_limit_order(order_id).unwrap();
+    assert_eq!(market.best_ask(), Some(price(99.0)));
 }
 
 #[test]
 fn test_partially_filled_limit_bid() {
     let mut market = Market::new("BTCUSD");
-    market.add_limit_ask(100.0, 5.0).unwrap();
-    let order_id = market.add_limit_bid(100.0, 10.0).unwrap();
-    assert_eq!(market.best_bid(), 100.0);
+    market.add_limit_ask(price(100.0), quantity(5.0)).unwrap();
+    let order_id = market
+        .add_limit_bid(price(100.0), quantity(10.0))
+        .unwrap()
+        .order_id();
+    assert_eq!(market.best_bid(), Some(price(100.0)));
     // Check if the partially filled order remains
     assert!(market.order_exists(order_id));
 }
@@ -151,9 +177,1199 @@ fn test_partially_filled_limit_bid() {
 #[test]
 fn test_partially_filled_limit_ask() {
     let mut market = Market::new("BTCUSD");
-    market.add_limit_bid(100.0, 5.0).unwrap();
-    let order_id = market.add_limit_ask(100.0, 10.0).unwrap();
-    assert_eq!(market.best_ask(), 100.0);
+    market.add_limit_bid(price(100.0), quantity(5.0)).unwrap();
+    let order_id = market
+        .add_limit_ask(price(100.0), quantity(10.0))
+        .unwrap()
+        .order_id();
+    assert_eq!(market.best_ask(), Some(price(100.0)));
     // Check if the partially filled order remains
     assert!(market.order_exists(order_id));
 }
+
+#[test]
+fn test_price_precision() {
+    assert_eq!(price(0.07), Price::from_ticks(7));
+    assert_eq!(Price::from_f64(100.001, 2), None);
+    assert_eq!(Quantity::from_f64(-1.0, 2), None);
+}
+
+#[test]
+fn test_add_limit_bid_with_small_price() {
+    let mut market = Market::new("BTCUSD");
+    market.add_limit_bid(price(0.07), quantity(1.0)).unwrap();
+    assert_eq!(market.best_bid(), Some(price(0.07)));
+}
+
+#[test]
+fn test_market_bid_leaves_exact_remainder() {
+    let mut market = Market::new("BTCUSD");
+    market.add_limit_ask(price(100.0), quantity(5.0)).unwrap();
+    market.add_limit_ask(price(101.0), quantity(5.0)).unwrap();
+
+    assert!(market.add_market_bid(quantity(4.99)).unwrap().is_filled());
+    assert!(market.add_market_bid(quantity(0.01)).unwrap().is_filled());
+    assert_eq!(market.best_ask(), Some(price(101.0)));
+
+    let report = market.add_market_bid(quantity(5.02)).unwrap();
+    assert_eq!(report.filled_quantity(), quantity(5.0));
+    assert_eq!(report.cancelled_quantity(), quantity(0.02));
+    assert_eq!(market.best_ask(), None);
+}
+
+#[test]
+fn test_limit_bid_does_not_trade_through_price() {
+    let mut market = Market::new("BTCUSD");
+    market.add_limit_ask(price(100.0), quantity(5.0)).unwrap();
+    market.add_limit_ask(price(101.0), quantity(5.0)).unwrap();
+
+    let order_id = market
+        .add_limit_bid(price(100.0), quantity(10.0))
+        .unwrap()
+        .order_id();
+
+    assert!(market.order_exists(order_id));
+    assert_eq!(market.best_bid(), Some(price(100.0)));
+    assert_eq!(market.best_ask(), Some(price(101.0)));
+}
+
+#[test]
+fn test_limit_ask_does_not_trade_through_price() {
+    let mut market = Market::new("BTCUSD");
+    market.add_limit_bid(price(101.0), quantity(5.0)).unwrap();
+    market.add_limit_bid(price(100.0), quantity(5.0)).unwrap();
+
+    let order_id = market
+        .add_limit_ask(price(101.0), quantity(10.0))
+        .unwrap()
+        .order_id();
+
+    assert!(market.order_exists(order_id));
+    assert_eq!(market.best_ask(), Some(price(101.0)));
+    assert_eq!(market.best_bid(), Some(price(100.0)));
+}
+
+#[test]
+fn test_limit_bid_reports_fills() {
+    let mut market = Market::new("BTCUSD");
+    let first_ask = market.add_limit_ask(price(100.0), quantity(5.0)).unwrap();
+    let second_ask = market.add_limit_ask(price(101.0), quantity(5.0)).unwrap();
+
+    let report = market.add_limit_bid(price(101.0), quantity(12.0)).unwrap();
+
+    let fills = report.fills();
+    assert_eq!(fills.len(), 2);
+
+    assert_eq!(fills[0].aggressor_id(), report.order_id());
+    assert_eq!(fills[0].passive_id(), first_ask.order_id());
+    assert_eq!(fills[0].price(), price(100.0));
+    assert_eq!(fills[0].quantity(), quantity(5.0));
+    assert_eq!(fills[0].aggressor_side(), OrderSide::Bid);
+
+    assert_eq!(fills[1].passive_id(), second_ask.order_id());
+    assert_eq!(fills[1].price(), price(101.0));
+    assert!(fills[1].trade_id() > fills[0].trade_id());
+
+    let resting = report.resting().unwrap();
+    assert_eq!(resting.id(), report.order_id());
+    assert_eq!(resting.price(), price(101.0));
+    assert_eq!(resting.quantity(), quantity(2.0));
+    assert!(market.order_exists(report.order_id()));
+}
+
+#[test]
+fn test_market_ask_reports_partial_fill_of_passive_order() {
+    let mut market = Market::new("BTCUSD");
+    let bid = market.add_limit_bid(price(100.0), quantity(5.0)).unwrap();
+
+    let report = market.add_market_ask(quantity(2.0)).unwrap();
+
+    assert!(report.is_filled());
+    assert!(report.resting().is_none());
+    assert_eq!(report.fills().len(), 1);
+    assert_eq!(report.fills()[0].passive_id(), bid.order_id());
+    assert_eq!(report.fills()[0].aggressor_side(), OrderSide::Ask);
+    assert!(market.order_exists(bid.order_id()));
+}
+
+#[test]
+fn test_cancel_returns_order() {
+    let mut market = Market::new("BTCUSD");
+    let order_id = market
+        .add_limit_ask(price(100.0), quantity(10.0))
+        .unwrap()
+        .order_id();
+
+    let order = market.cancel_limit_order(order_id).unwrap();
+
+    assert_eq!(order.id(), order_id);
+    assert_eq!(order.quantity(), quantity(10.0));
+    assert!(!market.order_exists(order_id));
+}
+
+#[test]
+fn test_reject_invalid_orders() {
+    let config = MarketConfig {
+        tick_size: price(0.05),
+        lot_size: quantity(1.0),
+        ..MarketConfig::default()
+    };
+    let mut market = Market::with_config("BTCUSD", config).unwrap();
+
+    assert_eq!(
+        market.add_limit_bid(Price::from_ticks(0), quantity(1.0)),
+        Err(MarketError::InvalidPrice)
+    );
+    assert_eq!(
+        market.add_limit_bid(price(100.01), quantity(1.0)),
+        Err(MarketError::InvalidTick)
+    );
+    assert_eq!(
+        market.add_limit_ask(price(100.05), quantity(1.5)),
+        Err(MarketError::InvalidQuantity)
+    );
+    assert_eq!(
+        market.add_market_bid(Quantity::ZERO),
+        Err(MarketError::ZeroQuantity)
+    );
+    assert!(market.add_limit_ask(price(100.05), quantity(2.0)).is_ok());
+}
+
+#[test]
+fn test_reject_config_without_tick_or_lot_size() {
+    for config in [
+        MarketConfig {
+            tick_size: Price::from_ticks(0),
+            ..MarketConfig::default()
+        },
+        MarketConfig {
+            tick_size: Price::from_ticks(-5),
+            ..MarketConfig::default()
+        },
+        MarketConfig {
+            lot_size: Quantity::ZERO,
+            ..MarketConfig::default()
+        },
+    ] {
+        assert_eq!(
+            Market::with_config("BTCUSD", config).err(),
+            Some(MarketError::InvalidConfig)
+        );
+    }
+}
+
+#[test]
+fn test_reject_orders_overflowing_a_level() {
+    let mut market = Market::new("BTCUSD");
+    let large = Quantity::from_lots(u64::MAX - 1);
+
+    market.add_limit_ask(price(100.0), large).unwrap();
+
+    assert_eq!(
+        market.add_limit_ask(price(100.0), large),
+        Err(MarketError::LevelFull)
+    );
+    assert_eq!(market.level_quantity(OrderSide::Ask, price(100.0)), large);
+
+    // other levels and fill-or-kill checks across levels are unaffected
+    market.add_limit_ask(price(101.0), large).unwrap();
+
+    let bid = market
+        .add_order(
+            OrderRequest::market(OrderSide::Bid, Quantity::from_lots(u64::MAX))
+                .with_time_in_force(TimeInForce::FillOrKill),
+        )
+        .unwrap();
+
+    assert_eq!(bid.filled_quantity(), Quantity::from_lots(u64::MAX));
+}
+
+#[test]
+fn test_modify_counts_own_quantity_against_a_full_level() {
+    let mut market = Market::new("BTCUSD");
+
+    market
+        .add_limit_ask(price(100.0), Quantity::from_lots(u64::MAX - 10))
+        .unwrap();
+    let ask = market
+        .add_limit_ask(price(100.0), Quantity::from_lots(5))
+        .unwrap()
+        .order_id();
+
+    // the 5 lots the order already rests with are replaced, not added
+    market
+        .modify_order(ask, price(100.0), Quantity::from_lots(8))
+        .unwrap();
+    assert_eq!(
+        market.level_quantity(OrderSide::Ask, price(100.0)),
+        Quantity::from_lots(u64::MAX - 2)
+    );
+
+    assert_eq!(
+        market.modify_order(ask, price(100.0), Quantity::from_lots(11)),
+        Err(MarketError::LevelFull)
+    );
+    assert_eq!(
+        market.get_order(ask).unwrap().remaining_quantity(),
+        Quantity::from_lots(8)
+    );
+}
+
+#[test]
+fn test_halted_market_rejects_orders() {
+    let mut market = Market::new("BTCUSD");
+    let order_id = market
+        .add_limit_bid(price(100.0), quantity(1.0))
+        .unwrap()
+        .order_id();
+
+    market.halt().unwrap();
+
+    assert_eq!(
+        market.add_limit_bid(price(100.0), quantity(1.0)),
+        Err(MarketError::MarketHalted)
+    );
+    assert_eq!(
+        market.add_market_ask(quantity(1.0)),
+        Err(MarketError::MarketHalted)
+    );
+    assert!(market.cancel_limit_order(order_id).is_ok());
+
+    market.resume().unwrap();
+
+    assert!(market.add_limit_bid(price(100.0), quantity(1.0)).is_ok());
+}
+
+#[test]
+fn test_market_error_display() {
+    assert_eq!(MarketError::UnknownOrder(7).to_string(), "unknown order 7");
+}
+
+#[test]
+fn test_modify_unknown_order() {
+    let mut market = Market::new("BTCUSD");
+    assert_eq!(
+        market.modify_order(1, price(100.0), quantity(1.0)),
+        Err(MarketError::UnknownOrder(1))
+    );
+}
+
+#[test]
+fn test_modify_reduce_quantity_keeps_priority() {
+    let mut market = Market::new("BTCUSD");
+    let first = market
+        .add_limit_ask(price(100.0), quantity(5.0))
+        .unwrap()
+        .order_id();
+    let second = market
+        .add_limit_ask(price(100.0), quantity(5.0))
+        .unwrap()
+        .order_id();
+
+    let report = market
+        .modify_order(first, price(100.0), quantity(2.0))
+        .unwrap();
+    assert_eq!(report.order_id(), first);
+    assert_eq!(report.resting().unwrap().quantity(), quantity(2.0));
+
+    let report = market.add_market_bid(quantity(3.0)).unwrap();
+
+    assert_eq!(report.fills()[0].passive_id(), first);
+    assert_eq!(report.fills()[0].quantity(), quantity(2.0));
+    assert_eq!(report.fills()[1].passive_id(), second);
+    assert_eq!(report.fills()[1].quantity(), quantity(1.0));
+}
+
+#[test]
+fn test_modify_increase_quantity_loses_priority() {
+    let mut market = Market::new("BTCUSD");
+    let first = market
+        .add_limit_bid(price(100.0), quantity(5.0))
+        .unwrap()
+        .order_id();
+    let second = market
+        .add_limit_bid(price(100.0), quantity(5.0))
+        .unwrap()
+        .order_id();
+
+    market
+        .modify_order(first, price(100.0), quantity(6.0))
+        .unwrap();
+
+    let report = market.add_market_ask(quantity(5.0)).unwrap();
+
+    assert_eq!(report.fills().len(), 1);
+    assert_eq!(report.fills()[0].passive_id(), second);
+    assert!(market.order_exists(first));
+}
+
+#[test]
+fn test_modify_price_moves_order_and_can_trade() {
+    let mut market = Market::new("BTCUSD");
+    let ask = market
+        .add_limit_ask(price(101.0), quantity(5.0))
+        .unwrap()
+        .order_id();
+    let bid = market
+        .add_limit_bid(price(99.0), quantity(8.0))
+        .unwrap()
+        .order_id();
+
+    market
+        .modify_order(bid, price(100.0), quantity(8.0))
+        .unwrap();
+    assert_eq!(market.best_bid(), Some(price(100.0)));
+
+    let report = market
+        .modify_order(bid, price(101.0), quantity(8.0))
+        .unwrap();
+
+    assert_eq!(report.order_id(), bid);
+    assert_eq!(report.fills()[0].passive_id(), ask);
+    assert_eq!(report.resting().unwrap().quantity(), quantity(3.0));
+    assert_eq!(market.best_bid(), Some(price(101.0)));
+    assert_eq!(market.best_ask(), None);
+}
+
+#[test]
+fn test_immediate_or_cancel_does_not_rest() {
+    let mut market = Market::new("BTCUSD");
+    let ask = market
+        .add_limit_ask(price(100.0), quantity(5.0))
+        .unwrap()
+        .order_id();
+
+    let report = market
+        .add_order(
+            OrderRequest::limit(OrderSide::Bid, price(100.0), quantity(8.0))
+                .with_time_in_force(TimeInForce::ImmediateOrCancel),
+        )
+        .unwrap();
+
+    assert_eq!(report.fills()[0].passive_id(), ask);
+    assert_eq!(report.filled_quantity(), quantity(5.0));
+    assert_eq!(report.cancelled_quantity(), quantity(3.0));
+    assert!(report.resting().is_none());
+    assert!(!market.order_exists(report.order_id()));
+    assert_eq!(market.best_bid(), None);
+}
+
+#[test]
+fn test_fill_or_kill_without_enough_liquidity_leaves_book_untouched() {
+    let mut market = Market::new("BTCUSD");
+    let first = market
+        .add_limit_ask(price(100.0), quantity(5.0))
+        .unwrap()
+        .order_id();
+    market.add_limit_ask(price(102.0), quantity(5.0)).unwrap();
+
+    let report = market
+        .add_order(
+            OrderRequest::limit(OrderSide::Bid, price(101.0), quantity(6.0))
+                .with_time_in_force(TimeInForce::FillOrKill),
+        )
+        .unwrap();
+
+    assert!(report.fills().is_empty());
+    assert_eq!(report.cancelled_quantity(), quantity(6.0));
+    assert!(market.order_exists(first));
+    assert_eq!(market.best_ask(), Some(price(100.0)));
+}
+
+#[test]
+fn test_fill_or_kill_with_enough_liquidity_fills() {
+    let mut market = Market::new("BTCUSD");
+    market.add_limit_bid(price(100.0), quantity(5.0)).unwrap();
+    market.add_limit_bid(price(99.0), quantity(5.0)).unwrap();
+
+    let report = market
+        .add_order(
+            OrderRequest::market(OrderSide::Ask, quantity(7.0))
+                .with_time_in_force(TimeInForce::FillOrKill),
+        )
+        .unwrap();
+
+    assert!(report.is_filled());
+    assert_eq!(report.fills().len(), 2);
+    assert_eq!(market.best_bid(), Some(price(99.0)));
+}
+
+#[test]
+fn test_post_only_reject() {
+    let mut market = Market::new("BTCUSD");
+    market.add_limit_ask(price(100.0), quantity(5.0)).unwrap();
+
+    assert_eq!(
+        market.add_order(
+            OrderRequest::limit(OrderSide::Bid, price(100.0), quantity(1.0))
+                .with_post_only(PostOnly::Reject)
+        ),
+        Err(MarketError::WouldTakeLiquidity)
+    );
+
+    let report = market
+        .add_order(
+            OrderRequest::limit(OrderSide::Bid, price(99.99), quantity(1.0))
+                .with_post_only(PostOnly::Reject),
+        )
+        .unwrap();
+
+    assert!(report.fills().is_empty());
+    assert_eq!(market.best_bid(), Some(price(99.99)));
+    assert_eq!(market.best_ask(), Some(price(100.0)));
+}
+
+#[test]
+fn test_post_only_slide() {
+    let config = MarketConfig {
+        tick_size: price(0.05),
+        ..MarketConfig::default()
+    };
+    let mut market = Market::with_config("BTCUSD", config).unwrap();
+    market.add_limit_ask(price(100.0), quantity(5.0)).unwrap();
+    market.add_limit_bid(price(99.0), quantity(5.0)).unwrap();
+
+    let bid = market
+        .add_order(
+            OrderRequest::limit(OrderSide::Bid, price(101.0), quantity(1.0))
+                .with_post_only(PostOnly::Slide),
+        )
+        .unwrap();
+
+    assert!(bid.fills().is_empty());
+    assert_eq!(bid.resting().unwrap().price(), price(99.95));
+
+    let ask = market
+        .add_order(
+            OrderRequest::limit(OrderSide::Ask, price(98.0), quantity(1.0))
+                .with_post_only(PostOnly::Slide),
+        )
+        .unwrap();
+
+    assert!(ask.fills().is_empty());
+    assert_eq!(ask.resting().unwrap().price(), price(100.0));
+    assert_eq!(market.best_bid(), Some(price(99.95)));
+}
+
+#[test]
+fn test_post_only_slide_past_largest_price_is_rejected() {
+    let mut market = Market::new("BTCUSD");
+    let largest = Price::from_ticks(i64::MAX);
+    market.add_limit_bid(largest, quantity(1.0)).unwrap();
+
+    assert_eq!(
+        market.add_order(
+            OrderRequest::limit(OrderSide::Ask, largest, quantity(1.0))
+                .with_post_only(PostOnly::Slide),
+        ),
+        Err(MarketError::WouldTakeLiquidity)
+    );
+    assert_eq!(market.best_ask(), None);
+    assert_eq!(market.best_bid(), Some(largest));
+}
+
+#[test]
+fn test_stop_market_triggers_on_last_trade_price() {
+    let mut market = Market::new("BTCUSD");
+    market.add_limit_ask(price(100.0), quantity(1.0)).unwrap();
+    market.add_limit_ask(price(101.0), quantity(1.0)).unwrap();
+    market.add_limit_ask(price(102.0), quantity(5.0)).unwrap();
+
+    let stop = market
+        .add_order(OrderRequest::stop_market(
+            OrderSide::Bid,
+            price(101.0),
+            quantity(2.0),
+        ))
+        .unwrap();
+
+    assert!(stop.is_pending());
+    assert!(stop.fills().is_empty());
+    assert!(market.order_exists(stop.order_id()));
+
+    let first = market.add_market_bid(quantity(1.0)).unwrap();
+    assert_eq!(market.last_trade_price(), Some(price(100.0)));
+    assert!(first.triggered().is_empty());
+
+    let second = market.add_market_bid(quantity(1.0)).unwrap();
+    assert_eq!(second.triggered().len(), 1);
+
+    let triggered = &second.triggered()[0];
+    assert_eq!(triggered.order_id(), stop.order_id());
+    assert_eq!(triggered.fills().len(), 1);
+    assert_eq!(triggered.fills()[0].price(), price(102.0));
+    assert_eq!(triggered.filled_quantity(), quantity(2.0));
+    assert!(!market.order_exists(stop.order_id()));
+    assert_eq!(market.last_trade_price(), Some(price(102.0)));
+}
+
+#[test]
+fn test_stop_orders_cascade_in_trigger_order() {
+    let mut market = Market::new("BTCUSD");
+    market.add_limit_bid(price(100.0), quantity(1.0)).unwrap();
+    market.add_limit_bid(price(99.0), quantity(1.0)).unwrap();
+    market.add_limit_bid(price(98.0), quantity(1.0)).unwrap();
+    market.add_limit_bid(price(97.0), quantity(5.0)).unwrap();
+
+    let second = market
+        .add_order(OrderRequest::stop_market(
+            OrderSide::Ask,
+            price(98.0),
+            quantity(1.0),
+        ))
+        .unwrap();
+    let first = market
+        .add_order(OrderRequest::stop_market(
+            OrderSide::Ask,
+            price(99.0),
+            quantity(1.0),
+        ))
+        .unwrap();
+
+    let report = market.add_market_ask(quantity(2.0)).unwrap();
+    let triggered = report.triggered();
+
+    assert_eq!(triggered.len(), 2);
+    assert_eq!(triggered[0].order_id(), first.order_id());
+    assert_eq!(triggered[0].fills()[0].price(), price(98.0));
+    assert_eq!(triggered[1].order_id(), second.order_id());
+    assert_eq!(triggered[1].fills()[0].price(), price(97.0));
+    assert_eq!(market.best_bid(), Some(price(97.0)));
+}
+
+#[test]
+fn test_stop_limit_rests_after_trigger() {
+    let mut market = Market::new("BTCUSD");
+    market.add_limit_bid(price(100.0), quantity(1.0)).unwrap();
+
+    let stop = market
+        .add_order(OrderRequest::stop_limit(
+            OrderSide::Ask,
+            price(100.0),
+            price(101.0),
+            quantity(1.0),
+        ))
+        .unwrap();
+    assert_eq!(market.best_ask(), None);
+
+    let report = market.add_market_ask(quantity(1.0)).unwrap();
+    let triggered = &report.triggered()[0];
+
+    assert_eq!(triggered.order_id(), stop.order_id());
+    assert!(triggered.fills().is_empty());
+    assert_eq!(triggered.resting().unwrap().price(), price(101.0));
+    assert_eq!(market.best_ask(), Some(price(101.0)));
+}
+
+#[test]
+fn test_stop_already_reached_triggers_on_entry() {
+    let mut market = Market::new("BTCUSD");
+    market.add_limit_ask(price(100.0), quantity(2.0)).unwrap();
+    market.add_market_bid(quantity(1.0)).unwrap();
+
+    let stop = market
+        .add_order(OrderRequest::stop_market(
+            OrderSide::Bid,
+            price(99.0),
+            quantity(1.0),
+        ))
+        .unwrap();
+
+    assert!(!stop.is_pending());
+    assert_eq!(stop.filled_quantity(), quantity(1.0));
+}
+
+#[test]
+fn test_cancel_stop_order() {
+    let mut market = Market::new("BTCUSD");
+    market.add_limit_ask(price(100.0), quantity(2.0)).unwrap();
+
+    let stop = market
+        .add_order(OrderRequest::stop_market(
+            OrderSide::Bid,
+            price(100.0),
+            quantity(1.0),
+        ))
+        .unwrap();
+
+    let cancelled = market.cancel_limit_order(stop.order_id()).unwrap();
+    assert_eq!(cancelled.quantity(), quantity(1.0));
+    assert!(!market.order_exists(stop.order_id()));
+
+    let report = market.add_market_bid(quantity(1.0)).unwrap();
+    assert!(report.triggered().is_empty());
+    assert_eq!(
+        market.cancel_limit_order(stop.order_id()).unwrap_err(),
+        MarketError::UnknownOrder(stop.order_id())
+    );
+}
+
+#[test]
+fn test_iceberg_displays_only_peak() {
+    let mut market = Market::new("BTCUSD");
+
+    let iceberg = market
+        .add_order(
+            OrderRequest::limit(OrderSide::Ask, price(100.0), quantity(10.0))
+                .with_display_quantity(quantity(2.0)),
+        )
+        .unwrap();
+
+    assert_eq!(iceberg.resting().unwrap().quantity(), quantity(10.0));
+    assert_eq!(
+        market.level_quantity(OrderSide::Ask, price(100.0)),
+        quantity(2.0)
+    );
+
+    market.add_market_bid(quantity(1.0)).unwrap();
+    assert_eq!(
+        market.level_quantity(OrderSide::Ask, price(100.0)),
+        quantity(1.0)
+    );
+}
+
+#[test]
+fn test_iceberg_replenishes_behind_queue() {
+    let mut market = Market::new("BTCUSD");
+
+    let iceberg = market
+        .add_order(
+            OrderRequest::limit(OrderSide::Ask, price(100.0), quantity(5.0))
+                .with_display_quantity(quantity(2.0)),
+        )
+        .unwrap();
+    let visible = market.add_limit_ask(price(100.0), quantity(1.0)).unwrap();
+
+    let report = market.add_market_bid(quantity(4.0)).unwrap();
+    let passive_ids: Vec<u64> = report
+        .fills()
+        .iter()
+        .map(|fill| fill.passive_id())
+        .collect();
+
+    assert_eq!(
+        passive_ids,
+        vec![iceberg.order_id(), visible.order_id(), iceberg.order_id()]
+    );
+    assert_eq!(report.fills()[2].quantity(), quantity(1.0));
+    assert_eq!(
+        market.level_quantity(OrderSide::Ask, price(100.0)),
+        quantity(1.0)
+    );
+
+    // last slice holds the remaining reserve
+    let report = market.add_market_bid(quantity(5.0)).unwrap();
+    assert_eq!(report.filled_quantity(), quantity(2.0));
+    assert!(!market.order_exists(iceberg.order_id()));
+    assert_eq!(market.best_ask(), None);
+}
+
+#[test]
+fn test_fill_or_kill_counts_iceberg_reserve() {
+    let mut market = Market::new("BTCUSD");
+    market
+        .add_order(
+            OrderRequest::limit(OrderSide::Bid, price(100.0), quantity(6.0))
+                .with_display_quantity(quantity(1.0)),
+        )
+        .unwrap();
+
+    let report = market
+        .add_order(
+            OrderRequest::limit(OrderSide::Ask, price(100.0), quantity(6.0))
+                .with_time_in_force(TimeInForce::FillOrKill),
+        )
+        .unwrap();
+
+    assert!(report.is_filled());
+    assert_eq!(report.fills().len(), 6);
+}
+
+#[test]
+fn test_cancel_iceberg_returns_total_quantity() {
+    let mut market = Market::new("BTCUSD");
+
+    let iceberg = market
+        .add_order(
+            OrderRequest::limit(OrderSide::Bid, price(100.0), quantity(6.0))
+                .with_display_quantity(quantity(1.0)),
+        )
+        .unwrap();
+
+    let order = market.cancel_limit_order(iceberg.order_id()).unwrap();
+    assert_eq!(order.quantity(), quantity(6.0));
+    assert_eq!(order.displayed_quantity(), quantity(1.0));
+    assert_eq!(market.best_bid(), None);
+}
+
+#[test]
+fn test_depth_aggregates_levels() {
+    let mut market = Market::new("BTCUSD");
+    market.add_limit_bid(price(99.0), quantity(1.0)).unwrap();
+    market.add_limit_bid(price(99.0), quantity(2.0)).unwrap();
+    market.add_limit_bid(price(98.0), quantity(4.0)).unwrap();
+    market.add_limit_bid(price(97.0), quantity(8.0)).unwrap();
+    market.add_limit_ask(price(101.0), quantity(3.0)).unwrap();
+    market.add_limit_ask(price(102.0), quantity(5.0)).unwrap();
+
+    let depth = market.depth(2);
+
+    assert_eq!(
+        depth.bids(),
+        &[
+            DepthLevel::new(price(99.0), quantity(3.0), 2),
+            DepthLevel::new(price(98.0), quantity(4.0), 1),
+        ]
+    );
+    assert_eq!(
+        depth.asks(),
+        &[
+            DepthLevel::new(price(101.0), quantity(3.0), 1),
+            DepthLevel::new(price(102.0), quantity(5.0), 1),
+        ]
+    );
+    assert_eq!(market.bid_depth().count(), 3);
+}
+
+#[test]
+fn test_depth_skips_emptied_levels() {
+    let mut market = Market::new("BTCUSD");
+    market.add_limit_ask(price(100.0), quantity(1.0)).unwrap();
+    let cancelled = market.add_limit_ask(price(101.0), quantity(1.0)).unwrap();
+    market.add_limit_ask(price(102.0), quantity(1.0)).unwrap();
+
+    market.add_market_bid(quantity(1.0)).unwrap();
+    market.cancel_limit_order(cancelled.order_id()).unwrap();
+
+    let prices: Vec<Price> = market.ask_depth().map(|level| level.price()).collect();
+    assert_eq!(prices, vec![price(102.0)]);
+}
+
+#[test]
+fn test_get_order() {
+    let mut market = Market::new("BTCUSD");
+    let bid = market.add_limit_bid(price(99.0), quantity(5.0)).unwrap();
+    market.add_market_ask(quantity(2.0)).unwrap();
+
+    let order = market.get_order(bid.order_id()).unwrap();
+
+    assert_eq!(order.id(), bid.order_id());
+    assert_eq!(order.side(), OrderSide::Bid);
+    assert_eq!(order.price(), price(99.0));
+    assert_eq!(order.original_quantity(), quantity(5.0));
+    assert_eq!(order.remaining_quantity(), quantity(3.0));
+    assert_eq!(order.filled_quantity(), quantity(2.0));
+    assert!(order.entry_time() <= SystemTime::now());
+
+    market.add_market_ask(quantity(3.0)).unwrap();
+    assert_eq!(market.get_order(bid.order_id()), None);
+}
+
+#[test]
+fn test_modified_order_keeps_history_and_instructions() {
+    let mut market = Market::new("BTCUSD");
+    let bid = market
+        .add_order(
+            OrderRequest::limit(OrderSide::Bid, price(99.0), quantity(5.0))
+                .with_post_only(PostOnly::Reject),
+        )
+        .unwrap();
+    market.add_market_ask(quantity(2.0)).unwrap();
+
+    // losing priority re-enters the order with its history
+    market
+        .modify_order(bid.order_id(), price(98.0), quantity(4.0))
+        .unwrap();
+
+    let order = market.get_order(bid.order_id()).unwrap();
+
+    assert_eq!(order.original_quantity(), quantity(6.0));
+    assert_eq!(order.filled_quantity(), quantity(2.0));
+    assert_eq!(order.remaining_quantity(), quantity(4.0));
+    assert_eq!(order.post_only(), Some(PostOnly::Reject));
+    assert_eq!(order.time_in_force(), TimeInForce::GoodTillCancel);
+
+    // still post-only, so it cannot be moved across the spread
+    market.add_limit_ask(price(100.0), quantity(1.0)).unwrap();
+
+    assert_eq!(
+        market.modify_order(bid.order_id(), price(100.0), quantity(4.0)),
+        Err(MarketError::WouldTakeLiquidity)
+    );
+    assert_eq!(
+        market.get_order(bid.order_id()).unwrap().price(),
+        price(98.0)
+    );
+
+    // trades made on re-entry add to the fill history
+    let other = market.add_limit_bid(price(97.0), quantity(3.0)).unwrap();
+    market
+        .modify_order(other.order_id(), price(100.0), quantity(3.0))
+        .unwrap();
+
+    let order = market.get_order(other.order_id()).unwrap();
+
+    assert_eq!(order.original_quantity(), quantity(3.0));
+    assert_eq!(order.filled_quantity(), quantity(1.0));
+    assert_eq!(order.remaining_quantity(), quantity(2.0));
+    assert_eq!(order.post_only(), None);
+}
+
+#[test]
+fn test_increasing_a_partly_filled_order() {
+    let mut market = Market::new("BTCUSD");
+    let bid = market.add_limit_bid(price(99.0), quantity(10.0)).unwrap();
+    market.add_market_ask(quantity(4.0)).unwrap();
+
+    market
+        .modify_order(bid.order_id(), price(99.0), quantity(20.0))
+        .unwrap();
+
+    let order = market.get_order(bid.order_id()).unwrap();
+
+    assert_eq!(order.original_quantity(), quantity(24.0));
+    assert_eq!(order.filled_quantity(), quantity(4.0));
+    assert_eq!(order.remaining_quantity(), quantity(20.0));
+}
+
+#[test]
+fn test_owner_follows_order() {
+    let mut market = Market::new("BTCUSD");
+    let maker = Owner::new(7).with_trader(3);
+    let taker = Owner::new(9);
+
+    let ask = market
+        .add_order(
+            OrderRequest::limit(OrderSide::Ask, price(100.0), quantity(5.0)).with_owner(maker),
+        )
+        .unwrap();
+    let stop = market
+        .add_order(
+            OrderRequest::stop_market(OrderSide::Bid, price(105.0), quantity(1.0))
+                .with_owner(taker),
+        )
+        .unwrap();
+
+    assert_eq!(ask.owner(), maker);
+    assert_eq!(stop.owner(), taker);
+    assert_eq!(market.get_order(ask.order_id()).unwrap().owner(), maker);
+
+    let bid = market
+        .add_order(OrderRequest::market(OrderSide::Bid, quantity(2.0)).with_owner(taker))
+        .unwrap();
+
+    assert_eq!(bid.owner(), taker);
+    assert_eq!(bid.fills()[0].aggressor_owner(), taker);
+    assert_eq!(bid.fills()[0].passive_owner(), maker);
+
+    // orders without an owner belong to account 0
+    let anonymous = market.add_limit_bid(price(99.0), quantity(1.0)).unwrap();
+    assert_eq!(anonymous.owner(), Owner::default());
+
+    // a replaced order keeps its owner
+    market
+        .modify_order(ask.order_id(), price(101.0), quantity(4.0))
+        .unwrap();
+    assert_eq!(market.get_order(ask.order_id()).unwrap().owner(), maker);
+
+    assert_eq!(
+        market.cancel_limit_order(ask.order_id()).unwrap().owner(),
+        maker
+    );
+    assert_eq!(
+        market.cancel_limit_order(stop.order_id()).unwrap().owner(),
+        taker
+    );
+    assert_eq!(maker.account(), 7);
+    assert_eq!(maker.trader(), Some(3));
+}
+
+#[test]
+fn test_level_orders_in_queue_order() {
+    let mut market = Market::new("BTCUSD");
+    let first = market.add_limit_ask(price(100.0), quantity(1.0)).unwrap();
+    let second = market.add_limit_ask(price(100.0), quantity(2.0)).unwrap();
+    let third = market.add_limit_ask(price(100.0), quantity(3.0)).unwrap();
+
+    // losing priority moves the first order to the back of the queue
+    market
+        .modify_order(first.order_id(), price(100.0), quantity(4.0))
+        .unwrap();
+
+    let ids: Vec<u64> = market
+        .level_orders(OrderSide::Ask, price(100.0))
+        .map(|order| order.id())
+        .collect();
+
+    assert_eq!(
+        ids,
+        vec![second.order_id(), third.order_id(), first.order_id()]
+    );
+    assert_eq!(market.level_orders(OrderSide::Bid, price(100.0)).count(), 0);
+}
+
+#[test]
+fn test_emptied_levels_are_removed() {
+    let mut market = Market::new("BTCUSD");
+
+    for i in 0..100 {
+        let ask = market
+            .add_limit_ask(price(100.0 + i as f64), quantity(1.0))
+            .unwrap();
+        market.cancel_limit_order(ask.order_id()).unwrap();
+    }
+
+    assert_eq!(market.level_count(OrderSide::Ask), 0);
+    assert_eq!(market.best_ask(), None);
+
+    market.add_limit_ask(price(100.0), quantity(1.0)).unwrap();
+    market.add_limit_ask(price(101.0), quantity(1.0)).unwrap();
+    market.add_limit_ask(price(102.0), quantity(1.0)).unwrap();
+    market.add_market_bid(quantity(2.0)).unwrap();
+
+    assert_eq!(market.level_count(OrderSide::Ask), 1);
+    assert_eq!(market.best_ask(), Some(price(102.0)));
+}
+
+#[test]
+fn test_best_prices_follow_cancels() {
+    let mut market = Market::new("BTCUSD");
+    let best = market.add_limit_bid(price(100.0), quantity(1.0)).unwrap();
+    market.add_limit_bid(price(98.0), quantity(1.0)).unwrap();
+    let middle = market.add_limit_bid(price(99.0), quantity(1.0)).unwrap();
+
+    market.cancel_limit_order(best.order_id()).unwrap();
+    assert_eq!(market.best_bid(), Some(price(99.0)));
+
+    market.cancel_limit_order(middle.order_id()).unwrap();
+    assert_eq!(market.best_bid(), Some(price(98.0)));
+    assert_eq!(market.level_count(OrderSide::Bid), 1);
+}
+
+#[test]
+fn test_cancel_from_middle_of_queue_keeps_order() {
+    let mut market = Market::new("BTCUSD");
+    let ids: Vec<u64> = (0..5)
+        .map(|_| {
+            market
+                .add_limit_ask(price(100.0), quantity(1.0))
+                .unwrap()
+                .order_id()
+        })
+        .collect();
+
+    market.cancel_limit_order(ids[2]).unwrap();
+    market.cancel_limit_order(ids[4]).unwrap();
+    market.add_limit_ask(price(100.0), quantity(1.0)).unwrap();
+
+    let report = market.add_market_bid(quantity(3.0)).unwrap();
+    let passive_ids: Vec<u64> = report
+        .fills()
+        .iter()
+        .map(|fill| fill.passive_id())
+        .collect();
+
+    assert_eq!(passive_ids, vec![ids[0], ids[1], ids[3]]);
+    assert_eq!(market.level_orders(OrderSide::Ask, price(100.0)).count(), 1);
+}
+
+fn self_trade_market(prevention: SelfTradePrevention) -> Market {
+    let config = MarketConfig {
+        self_trade_prevention: Some(prevention),
+        ..MarketConfig::default()
+    };
+    Market::with_config("BTCUSD", config).unwrap()
+}
+
+fn limit(side: OrderSide, limit_price: f64, size: f64, account: u64) -> OrderRequest {
+    OrderRequest::limit(side, price(limit_price), quantity(size)).with_owner(Owner::new(account))
+}
+
+#[test]
+fn test_self_trade_cancel_newest() {
+    let mut market = self_trade_market(SelfTradePrevention::CancelNewest);
+    let other = market
+        .add_order(limit(OrderSide::Ask, 100.0, 2.0, 2))
+        .unwrap();
+    let own = market
+        .add_order(limit(OrderSide::Ask, 100.0, 5.0, 1))
+        .unwrap();
+
+    let bid = market
+        .add_order(limit(OrderSide::Bid, 100.0, 4.0, 1))
+        .unwrap();
+
+    // fills the other account first, then stops at its own order
+    assert_eq!(bid.fills().len(), 1);
+    assert_eq!(bid.fills()[0].passive_id(), other.order_id());
+    assert!(bid.resting().is_none());
+    assert_eq!(bid.cancelled_quantity(), quantity(2.0));
+    assert_eq!(bid.self_traded_quantity(), quantity(2.0));
+    assert!(bid.prevented().is_empty());
+
+    assert_eq!(
+        market
+            .get_order(own.order_id())
+            .unwrap()
+            .remaining_quantity(),
+        quantity(5.0)
+    );
+    assert_eq!(market.best_bid(), None);
+}
+
+#[test]
+fn test_self_trade_cancel_oldest() {
+    let mut market = self_trade_market(SelfTradePrevention::CancelOldest);
+    let own = market
+        .add_order(limit(OrderSide::Ask, 100.0, 3.0, 1))
+        .unwrap();
+    let other = market
+        .add_order(limit(OrderSide::Ask, 100.0, 2.0, 2))
+        .unwrap();
+
+    let bid = market
+        .add_order(limit(OrderSide::Bid, 100.0, 4.0, 1))
+        .unwrap();
+
+    // the resting order is cancelled and matching continues behind it
+    assert_eq!(bid.prevented().len(), 1);
+    assert_eq!(bid.prevented()[0].id(), own.order_id());
+    assert_eq!(bid.prevented()[0].quantity(), quantity(3.0));
+    assert!(!market.order_exists(own.order_id()));
+
+    assert_eq!(bid.fills().len(), 1);
+    assert_eq!(bid.fills()[0].passive_id(), other.order_id());
+    assert!(bid.self_traded_quantity().is_zero());
+    assert_eq!(bid.resting().unwrap().quantity(), quantity(2.0));
+    assert_eq!(market.best_ask(), None);
+}
+
+#[test]
+fn test_self_trade_cancel_both() {
+    let mut market = self_trade_market(SelfTradePrevention::CancelBoth);
+    let own = market
+        .add_order(limit(OrderSide::Ask, 100.0, 3.0, 1))
+        .unwrap();
+
+    let bid = market
+        .add_order(limit(OrderSide::Bid, 100.0, 5.0, 1))
+        .unwrap();
+
+    assert!(bid.fills().is_empty());
+    assert!(bid.resting().is_none());
+    assert_eq!(bid.cancelled_quantity(), quantity(5.0));
+    assert_eq!(bid.prevented()[0].quantity(), quantity(3.0));
+    assert!(!market.order_exists(own.order_id()));
+    assert_eq!(market.best_bid(), None);
+    assert_eq!(market.best_ask(), None);
+}
+
+#[test]
+fn test_self_trade_decrement_and_cancel() {
+    let mut market = self_trade_market(SelfTradePrevention::DecrementAndCancel);
+    let own = market
+        .add_order(limit(OrderSide::Ask, 100.0, 5.0, 1))
+        .unwrap();
+
+    // the smaller incoming order is cancelled and the resting order reduced
+    let bid = market
+        .add_order(limit(OrderSide::Bid, 100.0, 2.0, 1))
+        .unwrap();
+
+    assert!(bid.fills().is_empty());
+    assert_eq!(bid.cancelled_quantity(), quantity(2.0));
+    assert_eq!(bid.prevented()[0].quantity(), quantity(2.0));
+    assert_eq!(
+        market
+            .get_order(own.order_id())
+            .unwrap()
+            .remaining_quantity(),
+        quantity(3.0)
+    );
+    assert_eq!(
+        market.depth(1).asks(),
+        &[DepthLevel::new(price(100.0), quantity(3.0), 1)]
+    );
+
+    // the larger incoming order is reduced and rests
+    let bid = market
+        .add_order(limit(OrderSide::Bid, 100.0, 4.0, 1))
+        .unwrap();
+
+    assert!(bid.fills().is_empty());
+    assert_eq!(bid.self_traded_quantity(), quantity(3.0));
+    assert_eq!(bid.resting().unwrap().quantity(), quantity(1.0));
+    assert!(!market.order_exists(own.order_id()));
+}
+
+#[test]
+fn test_self_trade_prevention_skips_unowned_orders() {
+    let mut market = self_trade_market(SelfTradePrevention::CancelBoth);
+    market.add_limit_ask(price(100.0), quantity(1.0)).unwrap();
+
+    let bid = market.add_limit_bid(price(100.0), quantity(1.0)).unwrap();
+
+    assert!(bid.is_filled());
+    assert!(bid.prevented().is_empty());
+}
+
+#[test]
+fn test_fill_or_kill_ignores_own_liquidity() {
+    let mut market = self_trade_market(SelfTradePrevention::CancelOldest);
+    let own = market
+        .add_order(limit(OrderSide::Ask, 100.0, 5.0, 1))
+        .unwrap();
+    market
+        .add_order(limit(OrderSide::Ask, 101.0, 5.0, 2))
+        .unwrap();
+
+    let bid = market
+        .add_order(
+            limit(OrderSide::Bid, 101.0, 10.0, 1).with_time_in_force(TimeInForce::FillOrKill),
+        )
+        .unwrap();
+
+    // killed in full without touching the book
+    assert!(bid.fills().is_empty());
+    assert!(bid.prevented().is_empty());
+    assert_eq!(bid.cancelled_quantity(), quantity(10.0));
+    assert!(market.order_exists(own.order_id()));
+
+    // other participants' liquidity behind the own order still counts
+    market
+        .add_order(limit(OrderSide::Ask, 101.0, 5.0, 3))
+        .unwrap();
+
+    let bid = market
+        .add_order(
+            limit(OrderSide::Bid, 101.0, 10.0, 1).with_time_in_force(TimeInForce::FillOrKill),
+        )
+        .unwrap();
+
+    assert!(bid.is_filled());
+    assert_eq!(bid.prevented()[0].id(), own.order_id());
+
+    // under cancel newest the own order stops the fill
+    let mut market = self_trade_market(SelfTradePrevention::CancelNewest);
+    market
+        .add_order(limit(OrderSide::Ask, 100.0, 5.0, 2))
+        .unwrap();
+    market
+        .add_order(limit(OrderSide::Ask, 100.0, 5.0, 1))
+        .unwrap();
+    market
+        .add_order(limit(OrderSide::Ask, 101.0, 5.0, 2))
+        .unwrap();
+
+    let bid = market
+        .add_order(
+            limit(OrderSide::Bid, 101.0, 10.0, 1).with_time_in_force(TimeInForce::FillOrKill),
+        )
+        .unwrap();
+
+    assert!(bid.fills().is_empty());
+    assert_eq!(
+        market.level_quantity(OrderSide::Ask, price(100.0)),
+        quantity(10.0)
+    );
+}
@@ -0,0 +1,33 @@
+use trade_match::matching_engine::pool::Slab;
+
+#[test]
+fn test_slab_reuses_removed_slots() {
+    let mut slab = Slab::with_capacity(4);
+
+    let first = slab.insert("first");
+    let second = slab.insert("second");
+    assert_eq!(slab.len(), 2);
+
+    assert_eq!(slab.remove(first), Some("first"));
+    assert_eq!(slab.remove(first), None);
+    assert_eq!(slab.get(first), None);
+
+    let third = slab.insert("third");
+    assert_eq!(third, first);
+    assert_eq!(slab[third], "third");
+    assert_eq!(slab[second], "second");
+    assert_eq!(slab.len(), 2);
+}
+
+#[test]
+fn test_slab_preallocates_capacity() {
+    let mut slab = Slab::with_capacity(1_000);
+    let capacity = slab.capacity();
+
+    for value in 0..1_000 {
+        slab.insert(value);
+    }
+
+    assert!(capacity >= 1_000);
+    assert_eq!(slab.capacity(), capacity);
+}
@@ -0,0 +1,176 @@
+mod common;
+
+use common::{journal_path, price, quantity};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use trade_match::journal::{FsyncPolicy, Journal, JournalHeader, JournalReader};
+use trade_match::matching_engine::ladder_book::LadderBook;
+use trade_match::matching_engine::market::{Market, MarketConfig};
+use trade_match::matching_engine::order::{OrderRequest, OrderSide, Owner, SelfTradePrevention};
+use trade_match::matching_engine::order_book::OrderBook;
+use trade_match::replay::{self, fills, StateDigest};
+
+// Journal of a short session that trades, triggers a stop and cancels
+fn record(name: &str, last_quantity: f64) -> PathBuf {
+    let path = journal_path(&format!("replay-{}", name));
+
+    let mut market = Market::new("BTCUSD");
+    let header = JournalHeader::from_market(&market);
+    market.set_journal(Journal::open(&path, FsyncPolicy::Never, &header).unwrap());
+
+    market.add_limit_bid(price(10.0), quantity(5.0)).unwrap();
+    market.add_limit_bid(price(9.0), quantity(5.0)).unwrap();
+    market
+        .add_order(OrderRequest::stop_market(
+            OrderSide::Ask,
+            price(9.5),
+            quantity(2.0),
+        ))
+        .unwrap();
+    market.add_limit_ask(price(12.0), quantity(1.0)).unwrap();
+    market.add_market_ask(quantity(6.0)).unwrap();
+    market.cancel_limit_order(4).unwrap();
+    market
+        .add_limit_ask(price(11.0), quantity(last_quantity))
+        .unwrap();
+
+    path
+}
+
+// Digest after every command and all fills of a replay
+fn run<B: OrderBook>(path: &PathBuf, market: &mut Market<B>) -> (Vec<u64>, Vec<(u64, u64)>) {
+    let mut digests = Vec::new();
+    let mut trades = Vec::new();
+
+    replay::replay(
+        JournalReader::open(path).unwrap(),
+        market,
+        |entry, result, digest| {
+            digests.push(digest.value());
+            trades.extend(
+                fills(result)
+                    .iter()
+                    .map(|fill| (entry.sequence(), fill.trade_id())),
+            );
+        },
+    )
+    .unwrap();
+
+    (digests, trades)
+}
+
+#[test]
+fn test_replays_are_identical() {
+    let path = record("identical", 1.0);
+
+    let first = run(&path, &mut Market::new("BTCUSD"));
+    let second = run(&path, &mut Market::new("BTCUSD"));
+
+    assert_eq!(first, second);
+    assert_eq!(first.0.len(), 7);
+
+    // the market ask trades twice and the stop it triggers once more
+    assert_eq!(first.1, vec![(5, 1), (5, 2), (5, 3)]);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_digest_tracks_state() {
+    let path = record("tracks", 1.0);
+    let other_path = record("tracks-other", 2.0);
+
+    let (digests, _) = run(&path, &mut Market::new("BTCUSD"));
+    let (other_digests, _) = run(&other_path, &mut Market::new("BTCUSD"));
+
+    // the runs only differ in the last command
+    assert_eq!(digests[..6], other_digests[..6]);
+    assert_ne!(digests[6], other_digests[6]);
+
+    // the digest is rolling, every step moves it on even without a state change
+    let mut digest = StateDigest::new();
+    let market = Market::new("BTCUSD");
+    let initial = digest.value();
+
+    digest.update(&market);
+    let once = digest.value();
+    digest.update(&market);
+
+    assert_ne!(once, initial);
+    assert_ne!(digest.value(), once);
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&other_path).unwrap();
+}
+
+#[test]
+fn test_book_implementations_agree() {
+    let path = record("books", 1.0);
+    let config = MarketConfig::default();
+
+    let tree = run(&path, &mut Market::new("BTCUSD"));
+    let ladder = run(
+        &path,
+        &mut Market::with_book(
+            0,
+            "BTCUSD",
+            config,
+            LadderBook::new(price(1.0), price(100.0), config.tick_size),
+        )
+        .unwrap(),
+    );
+
+    assert_eq!(ladder, tree);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_replay_binary_uses_the_journal_market() {
+    let path = journal_path("replay-binary");
+
+    let config = MarketConfig {
+        self_trade_prevention: Some(SelfTradePrevention::CancelNewest),
+        ..MarketConfig::default()
+    };
+    let owner = Owner::new(7);
+
+    let mut market = Market::with_market_id(1, "AAPL", config).unwrap();
+    let header = JournalHeader::from_market(&market);
+    market.set_journal(Journal::open(&path, FsyncPolicy::Never, &header).unwrap());
+
+    let bid = market
+        .add_order(
+            OrderRequest::limit(OrderSide::Bid, price(10.0), quantity(5.0)).with_owner(owner),
+        )
+        .unwrap();
+    market
+        .add_order(
+            OrderRequest::limit(OrderSide::Ask, price(10.0), quantity(2.0)).with_owner(owner),
+        )
+        .unwrap();
+    market.cancel_limit_order(bid.order_id()).unwrap();
+    drop(market);
+
+    let mut expected = Market::with_market_id(1, "AAPL", config).unwrap();
+    let (digests, _) = run(&path, &mut expected);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_replay"))
+        .arg(&path)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    // the ask would trade with its own bid, and the cancel find no order,
+    // under the default config or another market id
+    assert!(output.status.success());
+    assert!(!stdout.contains("fill"));
+    assert!(!stdout.contains("reject"));
+    assert_eq!(
+        stdout.lines().last().unwrap(),
+        format!("digest {:016x}", digests.last().unwrap())
+    );
+
+    fs::remove_file(&path).unwrap();
+}
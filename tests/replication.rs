@@ -0,0 +1,409 @@
+mod common;
+
+use common::{directory, price, quantity};
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::net::TcpStream;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+use trade_match::codec::{read_frame, write_frame};
+use trade_match::journal::{self, FsyncPolicy, Journal, JournalHeader};
+use trade_match::matching_engine::command::Command as MarketCommand;
+use trade_match::matching_engine::exchange::Exchange;
+use trade_match::matching_engine::market::{Market, MarketConfig};
+use trade_match::matching_engine::order::{OrderRequest, OrderSide};
+use trade_match::replay::StateDigest;
+use trade_match::replication::{
+    Backup, Primary, Publisher, ReplicationMessage, REPLICATION_VERSION,
+};
+use trade_match::server::protocol::{Logon, Request, Response};
+
+// How long a test waits on replication before it is failed rather than left hanging
+const DEADLINE: Duration = Duration::from_secs(10);
+
+// Exchange listing AAPL and MSFT, journaled to `directory` if given
+fn exchange(directory: Option<&Path>) -> Exchange {
+    let mut exchange = Exchange::new();
+
+    for symbol in ["AAPL", "MSFT"] {
+        let market = exchange
+            .create_market(symbol, MarketConfig::default())
+            .unwrap();
+
+        if let Some(directory) = directory {
+            let path = journal::path(directory, symbol);
+
+            journal::replay(&path, market, 0).unwrap();
+            let header = JournalHeader::from_market(market);
+            market.set_journal(Journal::open(&path, FsyncPolicy::Never, &header).unwrap());
+        }
+    }
+
+    exchange
+}
+
+// Starts replicating the markets of a journaled exchange on loopback
+fn start_primary(directory: &Path, exchange: &mut Exchange) -> (String, Publisher) {
+    let primary = Primary::bind("127.0.0.1:0", directory, exchange).unwrap();
+    let address = primary.local_addr().unwrap().to_string();
+    let publisher = primary.publisher();
+
+    for symbol in ["AAPL", "MSFT"] {
+        exchange
+            .market_mut(symbol)
+            .unwrap()
+            .set_publisher(publisher.clone());
+    }
+
+    thread::spawn(move || primary.run());
+
+    (address, publisher)
+}
+
+// Rests an ask, half of which is taken by a bid, and returns the ask's id
+fn trade(exchange: &mut Exchange, symbol: &str, value: f64) -> u64 {
+    let ask = exchange
+        .add_order(
+            symbol,
+            OrderRequest::limit(OrderSide::Ask, price(value), quantity(2.0)),
+        )
+        .unwrap()
+        .order_id();
+
+    exchange
+        .add_order(
+            symbol,
+            OrderRequest::limit(OrderSide::Bid, price(value), quantity(1.0)),
+        )
+        .unwrap();
+
+    ask
+}
+
+fn digest(market: &Market) -> u64 {
+    let mut digest = StateDigest::new();
+    digest.update(market);
+    digest.value()
+}
+
+fn assert_in_step(primary: &Exchange, backup: &Exchange) {
+    for symbol in ["AAPL", "MSFT"] {
+        let (primary, backup) = (
+            primary.market(symbol).unwrap(),
+            backup.market(symbol).unwrap(),
+        );
+
+        assert_eq!(digest(backup), digest(primary));
+    }
+}
+
+// Polls a condition until it holds, panics once the deadline has passed
+fn wait_until(description: &str, condition: impl Fn() -> bool) {
+    let deadline = Instant::now() + DEADLINE;
+
+    while !condition() {
+        assert!(
+            Instant::now() < deadline,
+            "timed out waiting for {}",
+            description
+        );
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+// Waits for the backup to acknowledge everything the primary journaled
+fn wait_for_acks(publisher: &Publisher, exchange: &Exchange) {
+    for symbol in ["AAPL", "MSFT"] {
+        let market = exchange.market(symbol).unwrap();
+        let sequence = market.journal().unwrap().sequence();
+
+        wait_until("the backup to acknowledge", || {
+            publisher.acknowledged(market.market_id()) >= sequence
+        });
+    }
+}
+
+// trade-match process, killed when dropped so a failed test leaves none behind
+struct Process {
+    child: Child,
+    output: Receiver<String>,
+}
+
+impl Process {
+    fn start(args: &[&str]) -> Process {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_trade-match"))
+            .args(args)
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        // lines are read on their own thread so waiting on them has a deadline
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let (sender, output) = mpsc::channel();
+
+        thread::spawn(move || {
+            for line in stdout.lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Process { child, output }
+    }
+
+    // Waits for a line starting with `prefix`, returns the word after it
+    fn wait_for(&self, prefix: &str) -> String {
+        let deadline = Instant::now() + DEADLINE;
+
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let line = self
+                .output
+                .recv_timeout(timeout)
+                .unwrap_or_else(|_| panic!("timed out waiting for {:?}", prefix));
+
+            if let Some(rest) = line.strip_prefix(prefix) {
+                return rest.split_whitespace().next().unwrap_or("").to_string();
+            }
+        }
+    }
+
+    fn kill(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+// Sends an order to a trade-match server and returns its responses up to
+// the expected count
+fn order(address: &str, request: OrderRequest, responses: usize) -> Vec<Response> {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.set_read_timeout(Some(DEADLINE)).unwrap();
+    write_frame(&mut stream, &Logon { account: 1 }.encode()).unwrap();
+
+    let request = Request {
+        client_order_id: 1,
+        symbol: "AAPL".to_string(),
+        command: MarketCommand::NewOrder(request),
+    };
+    write_frame(&mut stream, &request.encode()).unwrap();
+
+    (0..responses)
+        .map(|_| Response::decode(&read_frame(&mut stream).unwrap().unwrap()).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_reject_messages_of_other_replication_versions() {
+    let ack = ReplicationMessage::Ack {
+        market_id: 1,
+        sequence: 7,
+    };
+    let mut bytes = ack.encode();
+    assert_eq!(ReplicationMessage::decode(&bytes).unwrap(), ack);
+
+    assert_eq!(bytes[0], REPLICATION_VERSION);
+    bytes[0] = REPLICATION_VERSION + 1;
+    assert!(ReplicationMessage::decode(&bytes).is_err());
+}
+
+#[test]
+fn test_backup_follows_primary_and_takes_over() {
+    let directory = directory("replication-follow");
+    let mut primary = exchange(Some(&directory));
+    let (address, publisher) = start_primary(&directory, &mut primary);
+
+    // commands from before the backup connects come from the journal
+    trade(&mut primary, "AAPL", 10.0);
+    let ask = trade(&mut primary, "MSFT", 20.0);
+
+    let mut backup = Backup::connect(&address, exchange(None)).unwrap();
+    let follower = thread::spawn(move || {
+        backup.run().unwrap();
+        backup
+    });
+
+    wait_until("the backup to connect", || publisher.is_connected());
+
+    trade(&mut primary, "AAPL", 11.0);
+    primary.cancel_order(ask).unwrap();
+
+    wait_for_acks(&publisher, &primary);
+
+    // losing the primary ends the backup's run
+    publisher.disconnect();
+    let backup = follower.join().unwrap();
+
+    assert_eq!(backup.position(1), 4);
+    assert_eq!(backup.position(2), 3);
+
+    let mut promoted = backup.promote();
+    assert_in_step(&primary, &promoted);
+
+    // the promoted backup issues the order ids the primary would have
+    let request = OrderRequest::limit(OrderSide::Bid, price(9.0), quantity(1.0));
+    assert_eq!(
+        promoted.add_order("AAPL", request).unwrap(),
+        primary.add_order("AAPL", request).unwrap()
+    );
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_backup_resumes_from_its_journal() {
+    let directory = directory("replication-resume-primary");
+    let backup_directory = directory.join("backup");
+    fs::create_dir_all(&backup_directory).unwrap();
+
+    let mut primary = exchange(Some(&directory));
+    let (address, publisher) = start_primary(&directory, &mut primary);
+
+    trade(&mut primary, "AAPL", 10.0);
+
+    let mut backup = Backup::connect(&address, exchange(Some(&backup_directory))).unwrap();
+    let follower = thread::spawn(move || backup.run());
+
+    wait_for_acks(&publisher, &primary);
+    publisher.disconnect();
+    follower.join().unwrap().unwrap();
+
+    // the primary carries on while the backup is away
+    trade(&mut primary, "AAPL", 11.0);
+    trade(&mut primary, "MSFT", 20.0);
+
+    let backup = exchange(Some(&backup_directory));
+    assert_eq!(
+        backup.market("AAPL").unwrap().journal().unwrap().sequence(),
+        2
+    );
+
+    let mut backup = Backup::connect(&address, backup).unwrap();
+    let follower = thread::spawn(move || {
+        backup.run().unwrap();
+        backup
+    });
+
+    wait_until("the backup to connect", || publisher.is_connected());
+
+    wait_for_acks(&publisher, &primary);
+    publisher.disconnect();
+
+    let backup = follower.join().unwrap().promote();
+    assert_in_step(&primary, &backup);
+
+    // the backup journal matches the primary's entry for entry
+    for symbol in ["AAPL", "MSFT"] {
+        assert_eq!(
+            fs::read(journal::path(&backup_directory, symbol)).unwrap(),
+            fs::read(journal::path(&directory, symbol)).unwrap()
+        );
+    }
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_primary_rejects_mismatched_backup() {
+    let directory = directory("replication-mismatch");
+    let mut primary = exchange(Some(&directory));
+    let (address, publisher) = start_primary(&directory, &mut primary);
+
+    let mut other = Exchange::new();
+    other
+        .create_market("TSLA", MarketConfig::default())
+        .unwrap();
+
+    // the primary closes the connection instead of streaming entries
+    let mut backup = Backup::connect(&address, other).unwrap();
+    let _ = backup.run();
+
+    assert_eq!(backup.position(1), 0);
+    assert!(!publisher.is_connected());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_backup_process_takes_over_from_primary_process() {
+    let directory = directory("replication-process-primary");
+    let backup_directory = directory.join("backup");
+    fs::create_dir_all(&backup_directory).unwrap();
+
+    let (journal, backup_journal) = (
+        directory.to_str().unwrap(),
+        backup_directory.to_str().unwrap(),
+    );
+
+    let mut primary = Process::start(&[
+        "--listen",
+        "127.0.0.1:0",
+        "--symbols",
+        "AAPL,MSFT",
+        "--journal",
+        journal,
+        "--fsync",
+        "never",
+        "--replicate",
+        "127.0.0.1:0",
+    ]);
+    let replication_address = primary.wait_for("Replicating to a backup on ");
+    let primary_address = primary.wait_for("Listening on ");
+
+    // one entry caught up from the journal, one streamed as it is journaled
+    let ask = OrderRequest::limit(OrderSide::Ask, price(10.0), quantity(2.0));
+    let bid = OrderRequest::limit(OrderSide::Bid, price(10.0), quantity(1.0));
+
+    let ask_id = match order(&primary_address, ask, 1)[0] {
+        Response::Accepted { order_id, .. } => order_id,
+        ref response => panic!("unexpected response {:?}", response),
+    };
+
+    let backup = Process::start(&[
+        "--listen",
+        "127.0.0.1:0",
+        "--symbols",
+        "AAPL,MSFT",
+        "--journal",
+        backup_journal,
+        "--fsync",
+        "never",
+        "--backup-of",
+        &replication_address,
+    ]);
+    backup.wait_for("Following the primary at ");
+
+    order(&primary_address, bid, 2);
+
+    let (path, backup_path) = (
+        journal::path(&directory, "AAPL"),
+        journal::path(&backup_directory, "AAPL"),
+    );
+    wait_until("the backup to journal the primary's entries", || {
+        fs::read(&backup_path).ok() == fs::read(&path).ok()
+    });
+
+    primary.kill();
+    let backup_address = backup.wait_for("Listening on ");
+
+    // the rest of the replicated ask trades on the promoted backup, with the
+    // order id the primary would have issued next
+    assert!(matches!(
+        order(&backup_address, bid, 2)[1],
+        Response::Fill { order_id, quantity: filled, .. }
+            if order_id == ask_id + 2 && filled == quantity(1.0)
+    ));
+
+    drop(backup);
+    fs::remove_dir_all(&directory).unwrap();
+}
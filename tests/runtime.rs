@@ -0,0 +1,176 @@
+mod common;
+
+use common::{price, quantity};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use trade_match::matching_engine::command::{Command, CommandResult};
+use trade_match::matching_engine::error::ExchangeError;
+use trade_match::matching_engine::exchange::Exchange;
+use trade_match::matching_engine::market::MarketConfig;
+use trade_match::matching_engine::order::{OrderRequest, OrderSide};
+use trade_match::runtime::ring_buffer::ring_buffer;
+use trade_match::runtime::*;
+
+fn engine(symbols: &[&str], threads: usize, capacity: usize) -> Engine {
+    let mut exchange = Exchange::new();
+
+    for symbol in symbols {
+        exchange
+            .create_market(symbol, MarketConfig::default())
+            .unwrap();
+    }
+
+    Engine::start(exchange, threads, capacity)
+}
+
+fn wait(engine: &mut Engine, count: usize) -> Vec<EngineResult> {
+    let mut results = Vec::new();
+
+    while results.len() < count {
+        match engine.poll() {
+            Some(result) => results.push(result),
+            None => thread::yield_now(),
+        }
+    }
+
+    results
+}
+
+#[test]
+fn test_ring_buffer_is_bounded_fifo() {
+    let (mut producer, mut consumer) = ring_buffer(3);
+    assert_eq!(producer.capacity(), 4);
+
+    for value in 0..4 {
+        producer.push(value).unwrap();
+    }
+
+    assert_eq!(producer.push(4), Err(4));
+    assert_eq!(consumer.pop(), Some(0));
+
+    producer.push(4).unwrap();
+    let values: Vec<i32> = std::iter::from_fn(|| consumer.pop()).collect();
+
+    assert_eq!(values, vec![1, 2, 3, 4]);
+    assert!(consumer.is_empty());
+}
+
+#[test]
+fn test_ring_buffer_across_threads() {
+    let (mut producer, mut consumer) = ring_buffer(64);
+
+    let writer = thread::spawn(move || {
+        for mut value in 0..100_000u64 {
+            while let Err(rejected) = producer.push(value) {
+                value = rejected;
+                thread::yield_now();
+            }
+        }
+    });
+
+    let mut expected = 0;
+
+    while expected < 100_000 {
+        match consumer.pop() {
+            Some(value) => {
+                assert_eq!(value, expected);
+                expected += 1;
+            }
+            None => thread::yield_now(),
+        }
+    }
+
+    writer.join().unwrap();
+}
+
+#[test]
+fn test_ring_buffer_drops_unread_items() {
+    struct Counted(Arc<AtomicUsize>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let (mut producer, consumer) = ring_buffer(8);
+
+    for _ in 0..5 {
+        assert!(producer.push(Counted(drops.clone())).is_ok());
+    }
+
+    drop(producer);
+    drop(consumer);
+    assert_eq!(drops.load(Ordering::SeqCst), 5);
+}
+
+#[test]
+fn test_engine_processes_symbols_on_workers() {
+    let mut engine = engine(&["AAPL", "MSFT", "TSLA"], 2, 16);
+    let symbols = ["AAPL", "MSFT", "TSLA"];
+
+    for (index, symbol) in symbols.iter().enumerate() {
+        let ask = OrderRequest::limit(OrderSide::Ask, price(100.0), quantity(1.0));
+        let bid = OrderRequest::market(OrderSide::Bid, quantity(1.0));
+
+        engine
+            .submit(index as u64 * 2, symbol, Command::NewOrder(ask))
+            .unwrap();
+        engine
+            .submit(index as u64 * 2 + 1, symbol, Command::NewOrder(bid))
+            .unwrap();
+    }
+
+    let mut results = wait(&mut engine, 6);
+    results.sort_by_key(|result| result.request_id);
+
+    for (index, result) in results.iter().enumerate() {
+        let report = match &result.result {
+            Ok(CommandResult::Executed(report)) => report,
+            other => panic!("unexpected result {:?}", other),
+        };
+
+        assert_eq!(result.request_id, index as u64);
+        assert_eq!(report.fills().len(), index % 2);
+    }
+
+    let markets = engine.shutdown();
+    let symbols: Vec<&str> = markets.iter().map(|market| market.symbol()).collect();
+
+    assert_eq!(symbols, vec!["AAPL", "MSFT", "TSLA"]);
+    assert!(markets.iter().all(|market| market.best_ask().is_none()));
+}
+
+#[test]
+fn test_engine_applies_backpressure() {
+    let mut engine = engine(&["AAPL"], 1, 4);
+
+    for request_id in 0..1_000 {
+        let request = OrderRequest::limit(OrderSide::Bid, price(100.0), quantity(1.0));
+        engine
+            .submit(request_id, "AAPL", Command::NewOrder(request))
+            .unwrap();
+    }
+
+    let results = wait(&mut engine, 1_000);
+    let request_ids: Vec<u64> = results.iter().map(|result| result.request_id).collect();
+
+    assert_eq!(request_ids, (0..1_000).collect::<Vec<u64>>());
+
+    let markets = engine.shutdown();
+    assert_eq!(markets[0].depth(1).bids()[0].quantity(), quantity(1_000.0));
+}
+
+#[test]
+fn test_engine_rejects_unknown_symbol() {
+    let mut engine = engine(&["AAPL"], 1, 4);
+
+    assert_eq!(
+        engine
+            .submit(1, "TSLA", Command::Cancel { order_id: 1 })
+            .unwrap_err(),
+        ExchangeError::UnknownSymbol("TSLA".to_string())
+    );
+}
@@ -0,0 +1,553 @@
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+use std::time::Duration;
+use trade_match::codec::{read_frame, write_frame};
+use trade_match::matching_engine::command::Command;
+use trade_match::matching_engine::error::MarketError;
+use trade_match::matching_engine::exchange::Exchange;
+use trade_match::matching_engine::market::MarketConfig;
+use trade_match::matching_engine::order::{OrderRequest, OrderSide, Owner, TimeInForce};
+use trade_match::matching_engine::price::Price;
+use trade_match::matching_engine::quantity::Quantity;
+use trade_match::server::protocol::*;
+use trade_match::server::Server;
+
+fn start_server() -> SocketAddr {
+    let mut exchange = Exchange::new();
+    exchange
+        .create_market("AAPL", MarketConfig::default())
+        .unwrap();
+    exchange
+        .create_market("MSFT", MarketConfig::default())
+        .unwrap();
+
+    let server = Server::bind("127.0.0.1:0", exchange).unwrap();
+    let address = server.local_addr().unwrap();
+
+    thread::spawn(move || server.run());
+
+    address
+}
+
+// Connects and logs on to an account
+fn connect(address: SocketAddr, account: u64) -> TcpStream {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write_frame(&mut stream, &Logon { account }.encode()).unwrap();
+    stream
+}
+
+fn send(stream: &mut TcpStream, client_order_id: u64, symbol: &str, command: Command) {
+    let request = Request {
+        client_order_id,
+        symbol: symbol.to_string(),
+        command,
+    };
+
+    write_frame(stream, &request.encode()).unwrap();
+}
+
+fn receive(stream: &mut TcpStream) -> Response {
+    let frame = read_frame(stream).unwrap().unwrap();
+    Response::decode(&frame).unwrap()
+}
+
+#[test]
+fn test_request_round_trip() {
+    let request = Request {
+        client_order_id: 42,
+        symbol: "AAPL".to_string(),
+        command: Command::Replace {
+            order_id: 7,
+            price: Price::from_ticks(10_050),
+            quantity: Quantity::from_lots(300),
+        },
+    };
+
+    assert_eq!(Request::decode(&request.encode()).unwrap(), request);
+}
+
+#[test]
+fn test_new_order_round_trip() {
+    let request = Request {
+        client_order_id: 43,
+        symbol: "MSFT".to_string(),
+        command: Command::NewOrder(
+            OrderRequest::market(OrderSide::Ask, Quantity::from_lots(100))
+                .with_time_in_force(TimeInForce::FillOrKill)
+                .with_owner(Owner::new(12).with_trader(5)),
+        ),
+    };
+
+    assert_eq!(Request::decode(&request.encode()).unwrap(), request);
+}
+
+#[test]
+fn test_stop_order_round_trip() {
+    let request = Request {
+        client_order_id: 44,
+        symbol: "MSFT".to_string(),
+        command: Command::NewOrder(OrderRequest::stop_limit(
+            OrderSide::Bid,
+            Price::from_ticks(10_100),
+            Price::from_ticks(10_150),
+            Quantity::from_lots(100),
+        )),
+    };
+
+    assert_eq!(Request::decode(&request.encode()).unwrap(), request);
+}
+
+#[test]
+fn test_reject_messages_of_other_protocol_versions() {
+    let mut request = Request {
+        client_order_id: 1,
+        symbol: "AAPL".to_string(),
+        command: Command::Cancel { order_id: 7 },
+    }
+    .encode();
+    assert_eq!(request[0], PROTOCOL_VERSION);
+
+    request[0] = PROTOCOL_VERSION + 1;
+    assert!(Request::decode(&request).is_err());
+
+    let mut response = Response::Accepted {
+        client_order_id: 1,
+        order_id: 2,
+    }
+    .encode();
+    response[0] = PROTOCOL_VERSION + 1;
+    assert!(Response::decode(&response).is_err());
+}
+
+#[test]
+fn test_logon_round_trip() {
+    let logon = Logon { account: 7 };
+
+    assert_eq!(Logon::decode(&logon.encode()).unwrap(), logon);
+    assert!(Logon::decode(&Logon { account: 0 }.encode()).is_err());
+}
+
+#[test]
+fn test_halt_is_not_an_order_entry_request() {
+    let request = Request {
+        client_order_id: 45,
+        symbol: "MSFT".to_string(),
+        command: Command::Halt,
+    };
+
+    assert!(Request::decode(&request.encode()).is_err());
+}
+
+#[test]
+fn test_order_entry_and_fills_over_loopback() {
+    let address = start_server();
+    let mut maker = connect(address, 1);
+    let mut taker = connect(address, 2);
+
+    send(
+        &mut maker,
+        1,
+        "AAPL",
+        Command::NewOrder(OrderRequest::limit(
+            OrderSide::Ask,
+            Price::from_ticks(10_000),
+            Quantity::from_lots(500),
+        )),
+    );
+
+    let ask_id = match receive(&mut maker) {
+        Response::Accepted {
+            client_order_id: 1,
+            order_id,
+        } => order_id,
+        response => panic!("unexpected response {:?}", response),
+    };
+
+    send(
+        &mut taker,
+        2,
+        "AAPL",
+        Command::NewOrder(OrderRequest::market(
+            OrderSide::Bid,
+            Quantity::from_lots(800),
+        )),
+    );
+
+    let bid_id = match receive(&mut taker) {
+        Response::Accepted {
+            client_order_id: 2,
+            order_id,
+        } => order_id,
+        response => panic!("unexpected response {:?}", response),
+    };
+
+    let taker_fill = receive(&mut taker);
+    let maker_fill = receive(&mut maker);
+
+    assert!(matches!(
+        taker_fill,
+        Response::Fill { order_id, quantity, aggressor: true, .. }
+            if order_id == bid_id && quantity == Quantity::from_lots(500)
+    ));
+    assert!(matches!(
+        maker_fill,
+        Response::Fill { order_id, price, aggressor: false, .. }
+            if order_id == ask_id && price == Price::from_ticks(10_000)
+    ));
+
+    assert_eq!(
+        receive(&mut taker),
+        Response::Cancelled {
+            client_order_id: 2,
+            order_id: bid_id,
+            quantity: Quantity::from_lots(300),
+        }
+    );
+}
+
+#[test]
+fn test_cancel_and_rejects_over_loopback() {
+    let address = start_server();
+    let mut client = connect(address, 1);
+
+    send(
+        &mut client,
+        1,
+        "MSFT",
+        Command::NewOrder(OrderRequest::limit(
+            OrderSide::Bid,
+            Price::from_ticks(5_000),
+            Quantity::from_lots(100),
+        )),
+    );
+
+    let order_id = match receive(&mut client) {
+        Response::Accepted { order_id, .. } => order_id,
+        response => panic!("unexpected response {:?}", response),
+    };
+
+    send(&mut client, 2, "MSFT", Command::Cancel { order_id });
+
+    assert_eq!(
+        receive(&mut client),
+        Response::Cancelled {
+            client_order_id: 2,
+            order_id,
+            quantity: Quantity::from_lots(100),
+        }
+    );
+
+    send(&mut client, 3, "MSFT", Command::Cancel { order_id });
+
+    assert_eq!(
+        receive(&mut client),
+        Response::Rejected {
+            client_order_id: 3,
+            reason: RejectReason::Market(MarketError::UnknownOrder(order_id)),
+        }
+    );
+
+    send(&mut client, 4, "TSLA", Command::Cancel { order_id });
+
+    assert_eq!(
+        receive(&mut client),
+        Response::Rejected {
+            client_order_id: 4,
+            reason: RejectReason::UnknownSymbol,
+        }
+    );
+
+    write_frame(&mut client, &[0xff]).unwrap();
+
+    assert_eq!(
+        receive(&mut client),
+        Response::Rejected {
+            client_order_id: 0,
+            reason: RejectReason::MalformedRequest,
+        }
+    );
+}
+
+#[test]
+fn test_sessions_only_touch_their_own_orders() {
+    let address = start_server();
+    let mut owner = connect(address, 1);
+    let mut other = connect(address, 2);
+
+    send(
+        &mut owner,
+        1,
+        "AAPL",
+        Command::NewOrder(OrderRequest::limit(
+            OrderSide::Ask,
+            Price::from_ticks(10_000),
+            Quantity::from_lots(500),
+        )),
+    );
+
+    let order_id = match receive(&mut owner) {
+        Response::Accepted { order_id, .. } => order_id,
+        response => panic!("unexpected response {:?}", response),
+    };
+
+    send(&mut other, 2, "AAPL", Command::Cancel { order_id });
+    send(
+        &mut other,
+        3,
+        "AAPL",
+        Command::Replace {
+            order_id,
+            price: Price::from_ticks(10_100),
+            quantity: Quantity::from_lots(500),
+        },
+    );
+
+    for client_order_id in [2, 3] {
+        assert_eq!(
+            receive(&mut other),
+            Response::Rejected {
+                client_order_id,
+                reason: RejectReason::Market(MarketError::UnknownOrder(order_id)),
+            }
+        );
+    }
+
+    // the order is untouched and its fills still go to its owner
+    send(
+        &mut other,
+        4,
+        "AAPL",
+        Command::NewOrder(OrderRequest::market(
+            OrderSide::Bid,
+            Quantity::from_lots(100),
+        )),
+    );
+
+    assert!(matches!(receive(&mut other), Response::Accepted { .. }));
+    assert!(matches!(
+        receive(&mut owner),
+        Response::Fill { order_id: filled, price, aggressor: false, .. }
+            if filled == order_id && price == Price::from_ticks(10_000)
+    ));
+
+    send(&mut owner, 5, "AAPL", Command::Cancel { order_id });
+
+    assert_eq!(
+        receive(&mut owner),
+        Response::Cancelled {
+            client_order_id: 5,
+            order_id,
+            quantity: Quantity::from_lots(400),
+        }
+    );
+}
+
+#[test]
+fn test_client_that_stops_reading_does_not_stall_others() {
+    let address = start_server();
+    let mut stalled = connect(address, 1);
+    stalled
+        .set_write_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+
+    let request = Request {
+        client_order_id: 1,
+        symbol: "MSFT".to_string(),
+        command: Command::NewOrder(OrderRequest::market(
+            OrderSide::Bid,
+            Quantity::from_lots(100),
+        )),
+    }
+    .encode();
+
+    // floods the server without reading a response until the connection is
+    // dropped, or stops being read if the server waits on it
+    while write_frame(&mut stalled, &request).is_ok() {}
+
+    let mut client = connect(address, 2);
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    send(
+        &mut client,
+        2,
+        "AAPL",
+        Command::NewOrder(OrderRequest::limit(
+            OrderSide::Bid,
+            Price::from_ticks(5_000),
+            Quantity::from_lots(100),
+        )),
+    );
+
+    assert!(matches!(
+        receive(&mut client),
+        Response::Accepted {
+            client_order_id: 2,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_requests_before_logon_are_rejected() {
+    let address = start_server();
+    let mut client = TcpStream::connect(address).unwrap();
+    let order = Command::NewOrder(OrderRequest::limit(
+        OrderSide::Bid,
+        Price::from_ticks(5_000),
+        Quantity::from_lots(100),
+    ));
+
+    send(&mut client, 1, "AAPL", order);
+
+    assert_eq!(
+        receive(&mut client),
+        Response::Rejected {
+            client_order_id: 0,
+            reason: RejectReason::MalformedRequest,
+        }
+    );
+
+    write_frame(&mut client, &Logon { account: 1 }.encode()).unwrap();
+    send(&mut client, 2, "AAPL", order);
+
+    assert!(matches!(
+        receive(&mut client),
+        Response::Accepted {
+            client_order_id: 2,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_orders_are_cancelled_after_a_reconnect() {
+    let address = start_server();
+    let mut client = connect(address, 7);
+
+    send(
+        &mut client,
+        1,
+        "AAPL",
+        Command::NewOrder(OrderRequest::limit(
+            OrderSide::Ask,
+            Price::from_ticks(10_000),
+            Quantity::from_lots(500),
+        )),
+    );
+
+    let order_id = match receive(&mut client) {
+        Response::Accepted { order_id, .. } => order_id,
+        response => panic!("unexpected response {:?}", response),
+    };
+
+    drop(client);
+
+    // fills of the order go to the account's new session, which may cancel it
+    let mut client = connect(address, 7);
+    let mut taker = connect(address, 8);
+
+    send(
+        &mut taker,
+        2,
+        "AAPL",
+        Command::NewOrder(OrderRequest::market(
+            OrderSide::Bid,
+            Quantity::from_lots(100),
+        )),
+    );
+
+    assert!(matches!(receive(&mut taker), Response::Accepted { .. }));
+    assert!(matches!(
+        receive(&mut client),
+        Response::Fill { order_id: filled, aggressor: false, .. } if filled == order_id
+    ));
+
+    send(&mut client, 3, "AAPL", Command::Cancel { order_id });
+
+    assert_eq!(
+        receive(&mut client),
+        Response::Cancelled {
+            client_order_id: 3,
+            order_id,
+            quantity: Quantity::from_lots(400),
+        }
+    );
+}
+
+#[test]
+fn test_logon_takes_over_the_account() {
+    let address = start_server();
+    let mut first = connect(address, 7);
+    let mut second = connect(address, 7);
+
+    send(
+        &mut second,
+        1,
+        "AAPL",
+        Command::NewOrder(OrderRequest::limit(
+            OrderSide::Bid,
+            Price::from_ticks(5_000),
+            Quantity::from_lots(100),
+        )),
+    );
+
+    assert!(matches!(receive(&mut second), Response::Accepted { .. }));
+
+    // the earlier session is closed once the account logs on again
+    first
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    assert!(!matches!(read_frame(&mut first), Ok(Some(_))));
+}
+
+#[test]
+fn test_orders_cannot_name_another_account() {
+    let address = start_server();
+    let mut client = connect(address, 7);
+    let mut other = connect(address, 8);
+    let order = OrderRequest::limit(
+        OrderSide::Ask,
+        Price::from_ticks(10_000),
+        Quantity::from_lots(500),
+    );
+
+    send(
+        &mut client,
+        1,
+        "AAPL",
+        Command::NewOrder(order.with_owner(Owner::new(8))),
+    );
+
+    assert_eq!(
+        receive(&mut client),
+        Response::Rejected {
+            client_order_id: 1,
+            reason: RejectReason::AccountMismatch,
+        }
+    );
+
+    // naming its own account, or a trader within it, is accepted
+    send(
+        &mut client,
+        2,
+        "AAPL",
+        Command::NewOrder(order.with_owner(Owner::new(7).with_trader(3))),
+    );
+
+    let order_id = match receive(&mut client) {
+        Response::Accepted { order_id, .. } => order_id,
+        response => panic!("unexpected response {:?}", response),
+    };
+
+    send(&mut other, 3, "AAPL", Command::Cancel { order_id });
+
+    assert_eq!(
+        receive(&mut other),
+        Response::Rejected {
+            client_order_id: 3,
+            reason: RejectReason::Market(MarketError::UnknownOrder(order_id)),
+        }
+    );
+}
@@ -0,0 +1,228 @@
+mod common;
+
+use common::{directory, price, quantity};
+use std::fs;
+use std::io;
+use trade_match::codec::crc32;
+use trade_match::journal::{FsyncPolicy, Journal, JournalHeader, JournalReader};
+use trade_match::matching_engine::error::MarketError;
+use trade_match::matching_engine::market::{Market, MarketConfig, MARKET_ID_SHIFT};
+use trade_match::matching_engine::order::{OrderRequest, OrderSide, Owner, PostOnly};
+use trade_match::matching_engine::price::Price;
+use trade_match::matching_engine::quantity::Quantity;
+use trade_match::snapshot::{self, MarketSnapshot, SnapshotSchedule};
+
+fn orders(snapshot: &MarketSnapshot) -> Vec<(u64, Price, u64, Quantity, Quantity)> {
+    snapshot
+        .orders()
+        .iter()
+        .map(|order| {
+            (
+                order.id(),
+                order.price(),
+                order.sequence(),
+                order.remaining_quantity(),
+                order.displayed_quantity(),
+            )
+        })
+        .collect()
+}
+
+// Resting bids, an iceberg ask, a stop and some trades
+fn populate(market: &mut Market) {
+    market.add_limit_bid(price(10.0), quantity(5.0)).unwrap();
+    market.add_limit_bid(price(10.0), quantity(2.0)).unwrap();
+    market
+        .add_order(
+            OrderRequest::limit(OrderSide::Bid, price(9.5), quantity(1.0))
+                .with_post_only(PostOnly::Reject),
+        )
+        .unwrap();
+    market
+        .add_order(
+            OrderRequest::limit(OrderSide::Ask, price(11.0), quantity(10.0))
+                .with_display_quantity(quantity(2.0))
+                .with_owner(Owner::new(4).with_trader(2)),
+        )
+        .unwrap();
+    market
+        .add_order(OrderRequest::stop_limit(
+            OrderSide::Bid,
+            price(12.0),
+            price(12.5),
+            quantity(1.0),
+        ))
+        .unwrap();
+    market.add_market_bid(quantity(3.0)).unwrap();
+    market.add_market_ask(quantity(6.0)).unwrap();
+}
+
+#[test]
+fn test_snapshot_round_trip() {
+    let mut market = Market::new("BTCUSD");
+    populate(&mut market);
+
+    let snapshot = market.snapshot();
+    let decoded = MarketSnapshot::decode(&snapshot.encode()).unwrap();
+
+    assert_eq!(decoded, snapshot);
+    assert_eq!(decoded.orders().len(), 3);
+    assert_eq!(decoded.stops().len(), 1);
+
+    let mut restored = Market::new("BTCUSD");
+    restored.restore(&decoded).unwrap();
+
+    assert_eq!(restored.snapshot(), snapshot);
+    assert_eq!(restored.depth(usize::MAX), market.depth(usize::MAX));
+
+    // both markets carry on identically, including order ids and the iceberg reserve
+    let report = market.add_market_bid(quantity(9.0)).unwrap();
+    let restored_report = restored.add_market_bid(quantity(9.0)).unwrap();
+
+    assert_eq!(restored_report, report);
+    assert_eq!(restored.snapshot(), market.snapshot());
+}
+
+#[test]
+fn test_snapshot_rejects_damage() {
+    let mut market = Market::new("BTCUSD");
+    populate(&mut market);
+
+    let bytes = market.snapshot().encode();
+
+    let mut corrupt = bytes.clone();
+    let last = corrupt.len() - 1;
+    corrupt[last] ^= 1;
+    assert!(MarketSnapshot::decode(&corrupt).is_err());
+
+    // the version follows the magic bytes
+    let mut future = bytes.clone();
+    future[5] += 1;
+    assert!(MarketSnapshot::decode(&future).is_err());
+
+    assert!(MarketSnapshot::decode(&bytes[..bytes.len() - 1]).is_err());
+}
+
+#[test]
+fn test_orders_rejected_once_order_ids_run_out() {
+    let market = Market::with_market_id(3, "BTCUSD", MarketConfig::default()).unwrap();
+    let mut bytes = market.snapshot().encode();
+
+    // the order counter follows the sequence, symbol, market id and config in
+    // the body, which starts after the magic, version, length and checksum
+    let counter = 14 + 8 + 7 + 2 + 8 + 8 + 8 + 1;
+    bytes[counter..counter + 8].copy_from_slice(&((1u64 << MARKET_ID_SHIFT) - 2).to_be_bytes());
+    let checksum = crc32(&bytes[14..]);
+    bytes[10..14].copy_from_slice(&checksum.to_be_bytes());
+
+    let mut restored = Market::with_market_id(3, "BTCUSD", MarketConfig::default()).unwrap();
+    restored
+        .restore(&MarketSnapshot::decode(&bytes).unwrap())
+        .unwrap();
+
+    // the last order id stays within the market's own id space
+    let report = restored.add_limit_bid(price(10.0), quantity(1.0)).unwrap();
+    assert_eq!(report.order_id(), (4 << MARKET_ID_SHIFT) - 1);
+
+    assert_eq!(
+        restored.add_limit_ask(price(11.0), quantity(1.0)),
+        Err(MarketError::OrderIdsExhausted)
+    );
+    assert_eq!(restored.depth(usize::MAX).asks().len(), 0);
+}
+
+#[test]
+fn test_recover_from_snapshot_and_journal_tail() {
+    let directory = directory("snapshot-recover");
+    let journal_path = directory.join("BTCUSD.journal");
+
+    let mut market = Market::new("BTCUSD");
+    let header = JournalHeader::from_market(&market);
+    market.set_journal(Journal::open(&journal_path, FsyncPolicy::Never, &header).unwrap());
+    market.set_snapshot_schedule(SnapshotSchedule::new(&directory, 2));
+
+    populate(&mut market);
+    market.add_limit_ask(price(13.0), quantity(4.0)).unwrap();
+
+    // snapshots are taken after records 2, 4 and 6, the oldest is pruned
+    let snapshots = snapshot::list(&directory, "BTCUSD").unwrap();
+    let sequences: Vec<u64> = snapshots.iter().map(|(sequence, _)| *sequence).collect();
+
+    assert_eq!(sequences, vec![6, 4]);
+    assert_eq!(market.journal().unwrap().sequence(), 8);
+
+    let expected = market.snapshot();
+    drop(market);
+
+    let mut recovered = Market::new("BTCUSD");
+
+    assert_eq!(
+        snapshot::recover(&mut recovered, &directory, &journal_path).unwrap(),
+        8
+    );
+
+    let header = JournalHeader::from_market(&recovered);
+    recovered.set_journal(Journal::open(&journal_path, FsyncPolicy::Never, &header).unwrap());
+
+    // orders replayed from the journal are stamped with a new entry time
+    let actual = recovered.snapshot();
+
+    assert_eq!(actual.sequence(), expected.sequence());
+    assert_eq!(actual.stops(), expected.stops());
+    assert_eq!(orders(&actual), orders(&expected));
+    assert_eq!(recovered.last_trade_price(), Some(price(10.0)));
+
+    assert_eq!(recovered.depth(usize::MAX).asks().len(), 2);
+
+    drop(recovered);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_recover_rejects_journal_behind_snapshot() {
+    let directory = directory("snapshot-behind");
+    let journal_path = directory.join("BTCUSD.journal");
+
+    let mut market = Market::new("BTCUSD");
+    let header = JournalHeader::from_market(&market);
+    market.set_journal(Journal::open(&journal_path, FsyncPolicy::Every(100), &header).unwrap());
+    market.set_snapshot_schedule(SnapshotSchedule::new(&directory, 4));
+
+    for index in 0..5 {
+        market
+            .add_limit_bid(price(100.0 + index as f64), quantity(1.0))
+            .unwrap();
+    }
+
+    drop(market);
+
+    // lose the journal records after the second one
+    let mut reader = JournalReader::open(&journal_path).unwrap();
+    reader.by_ref().take(2).for_each(|entry| {
+        entry.unwrap();
+    });
+    let length = reader.valid_length();
+    drop(reader);
+
+    fs::OpenOptions::new()
+        .write(true)
+        .open(&journal_path)
+        .unwrap()
+        .set_len(length)
+        .unwrap();
+
+    let error =
+        snapshot::recover(&mut Market::new("BTCUSD"), &directory, &journal_path).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+    // a missing journal is behind the snapshot too
+    let error = snapshot::recover(
+        &mut Market::new("BTCUSD"),
+        &directory,
+        directory.join("missing"),
+    )
+    .unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+    fs::remove_dir_all(&directory).unwrap();
+}